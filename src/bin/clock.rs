@@ -13,7 +13,9 @@
 
 extern crate panic_semihosting; // logs messages to the host stderr; requires a debugger
 
+use timer::runner::Runner;
 use timer::systick;
+use timer::time::Duration;
 
 use f3::hal::prelude::*;
 use f3::hal::stm32f30x;
@@ -35,42 +37,39 @@ fn main() -> ! {
 
     // enable (power on) leds
     let mut leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
-    let num_leds = &leds.len();
+    let num_leds = leds.len();
 
     // set up system timer using default settings of 8 MHz
     let hal_clocks = rcc.cfgr.freeze(&mut flash.acr);
-    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, 6).unwrap();
+    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, Duration::from_ms(6)).unwrap();
 
-    let led_period = 1000;
-    let toggle_offset = 500;
-    let start_delay = 1000;
-    let off_delay = 3000;
+    let led_period = Duration::from_ms(1000);
+    let toggle_offset = Duration::from_ms(500);
+    let start_delay = Duration::from_ms(1000);
+    let off_delay = Duration::from_ms(3000);
 
     let mut on_idx = 0;
     let mut off_idx = 0;
-    let mut next_on = start_delay;
-    let mut next_off = next_on + off_delay + toggle_offset;
+
+    let mut runner: Runner<2> = Runner::new(systick.now());
+    // there's room for both, so these can't fail
+    let turn_on = runner.add(led_period, start_delay).unwrap();
+    let turn_off = runner
+        .add(led_period, start_delay + off_delay + toggle_offset)
+        .unwrap();
 
     loop {
-        // Turn on the next led every second
-        if systick.now() >= next_on {
-            leds[on_idx].on();
-            next_on += led_period;
-            if on_idx < num_leds - 1 {
-                on_idx += 1;
-            } else {
-                on_idx = 0;
+        while let Some(task) = runner.poll(systick.now()) {
+            // Turn on the next led every second
+            if task == turn_on {
+                leds[on_idx].on();
+                on_idx = (on_idx + 1) % num_leds;
             }
-        }
 
-        // Turn off the next led every second
-        if systick.now() >= next_off {
-            leds[off_idx].off();
-            next_off += led_period;
-            if off_idx < num_leds - 1 {
-                off_idx += 1;
-            } else {
-                off_idx = 0;
+            // Turn off the next led every second
+            if task == turn_off {
+                leds[off_idx].off();
+                off_idx = (off_idx + 1) % num_leds;
             }
         }
         systick.wait_til_wrapped();
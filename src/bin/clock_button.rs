@@ -13,15 +13,17 @@
 
 extern crate panic_semihosting; // logs messages to the host stderr; requires a debugger
 
-use timer::button::{ButtonEvent, Buttons};
+use timer::button::{ActiveLevel, Button, ButtonEvent};
+use timer::debounce::Lockout;
+use timer::scheduler::{Handle, Scheduler};
 use timer::systick;
-use timer::Milliseconds;
+use timer::time::{Duration, Instant};
 
 use f3::hal::prelude::*;
 use f3::hal::stm32f30x;
 
-// use f3::hal::gpio::gpioa;
-// use f3::hal::gpio::{Input, Floating};
+use f3::hal::gpio::gpioa::PA0;
+use f3::hal::gpio::{Floating, Input};
 use f3::led::Leds;
 
 use cortex_m_rt::entry;
@@ -47,11 +49,17 @@ fn main() -> ! {
     let leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
 
     // create a snake to maintain the state of the board
-    let mut snake = LedSnake::new(leds, Buttons::pa0(pa0, 0), 1000, 500, 4);
+    let mut snake = LedSnake::new(
+        leds,
+        Button::new(pa0, ActiveLevel::High, Lockout::new(Duration::ZERO)),
+        Duration::from_ms(1000),
+        Duration::from_ms(500),
+        4,
+    );
 
     // set up system timer using default settings of 8 MHz
     let hal_clocks = rcc.cfgr.freeze(&mut flash.acr);
-    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, 6).unwrap();
+    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, Duration::from_ms(6)).unwrap();
 
     // update the board each time the systick timer wraps
     loop {
@@ -64,57 +72,59 @@ struct LedSnake {
     leds: Leds,
     on_idx: usize,
     off_idx: usize,
-    button: Buttons,
-    period: Milliseconds,
-    next_on: Milliseconds,
-    next_off: Milliseconds,
+    button: Button<PA0<Input<Floating>>>,
+    schedule: Scheduler<2>,
+    /// Turns on the next LED each period
+    next_on: Handle,
+    /// Turns off the next LED each period
+    next_off: Handle,
     running: bool,
+    /// When the snake was last stopped
+    paused_at: Instant,
 }
 
 impl LedSnake {
     fn new(
         leds: Leds,
-        button: Buttons,
-        period: Milliseconds,
-        offset: Milliseconds,
+        button: Button<PA0<Input<Floating>>>,
+        period: Duration,
+        offset: Duration,
         max_on: u8,
     ) -> LedSnake {
+        let start = Instant::from_ms(0);
+        let mut schedule = Scheduler::new();
+        // there's room for both, so these can't fail
+        let next_on = schedule.every(start, period).unwrap();
+        let next_off = schedule
+            .every(start + period * u32::from(max_on - 1) + offset, period)
+            .unwrap();
         LedSnake {
             leds,
             on_idx: 0,
             off_idx: 0,
             button,
-            period,
-            next_on: 0,
-            next_off: period * (max_on - 1) as Milliseconds + offset,
+            schedule,
+            next_on,
+            next_off,
             running: true,
+            paused_at: Instant::from_ms(0),
         }
     }
 
-    fn update(&mut self, now: Milliseconds) {
+    fn update(&mut self, now: Instant) {
         if self.running {
-            // if its time to turn one on
-            // turn on and set next index and time on
-            if now >= self.next_on {
-                self.leds[self.on_idx].on();
-                self.next_on += self.period;
-                // TODO: change to (on_idx +1) % 8
-                if self.on_idx < 7 {
-                    self.on_idx += 1;
-                } else {
-                    self.on_idx = 0;
+            while let Some(expired) = self.schedule.poll(now) {
+                // if its time to turn one on
+                // turn on and set next index
+                if expired == self.next_on {
+                    self.leds[self.on_idx].on();
+                    self.on_idx = (self.on_idx + 1) % 8;
                 }
-            }
-            // if its time to turn one off
-            // turn on and set next index and time on
-            if now >= self.next_off {
-                self.leds[self.off_idx].off();
-                self.next_off += self.period;
-                // TODO: change to (on_idx +1) % 8
-                if self.off_idx < 7 {
-                    self.off_idx += 1;
-                } else {
-                    self.off_idx = 0;
+                // if its time to turn one off
+                // turn off and set next index
+                if expired == self.next_off {
+                    self.leds[self.off_idx].off();
+                    self.off_idx = (self.off_idx + 1) % 8;
                 }
             }
         }
@@ -126,13 +136,14 @@ impl LedSnake {
     }
 
     // toggles whether the snake is running or not
-    fn toggle(&mut self, now: Milliseconds) {
+    fn toggle(&mut self, now: Instant) {
         if self.running {
-            self.next_on -= now;
-            self.next_off -= now;
+            self.paused_at = now;
         } else {
-            self.next_on += now;
-            self.next_off += now;
+            // push back the schedule by however long we were stopped
+            let paused = now - self.paused_at;
+            self.schedule.delay(self.next_on, paused);
+            self.schedule.delay(self.next_off, paused);
         }
         self.running = !self.running;
     }
@@ -9,16 +9,29 @@
 
 extern crate panic_semihosting; // logs messages to the host stderr; requires a debugger
 
-use timer::button::Buttons;
-use timer::systick::Systick;
+use timer::button::{ActiveLevel, Button};
+use timer::debounce::Lockout;
+use timer::systick::{self, Systick};
+use timer::time::Duration;
+use timer::watchdog::{IndependentWatchdog, Supervisor};
 use timer::SimpleTimer;
 
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal::blocking::delay::DelayMs;
+
 use f3::hal::prelude::*;
 use f3::hal::stm32f30x;
 
 use f3::led::Leds;
 
-use cortex_m_rt::entry;
+use cortex_m_rt::{entry, exception};
+
+/// Feeds the watchdog from the SysTick exception, so a hung main loop
+/// can't stop the hang from being noticed and recorded
+static SUPERVISOR: Mutex<RefCell<Option<Supervisor<IndependentWatchdog, 1>>>> =
+    Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
@@ -26,6 +39,8 @@ fn main() -> ! {
     let cp = cortex_m::Peripherals::take().unwrap();
     let dp = stm32f30x::Peripherals::take().unwrap();
 
+    let hung = IndependentWatchdog::take_starved().is_some();
+
     let mut flash = dp.FLASH.constrain();
     let mut rcc = dp.RCC.constrain();
 
@@ -37,12 +52,12 @@ fn main() -> ! {
     let pa0 = gpioa
         .pa0
         .into_floating_input(&mut gpioa.moder, &mut gpioa.pupdr);
-    let discovery_button = Buttons::pa0(pa0, 0);
+    let discovery_button = Button::new(pa0, ActiveLevel::High, Lockout::new(Duration::ZERO));
 
     let pc1 = gpioc
         .pc1
         .into_floating_input(&mut gpioc.moder, &mut gpioc.pupdr);
-    let knob_button = Buttons::pc1(pc1, 0);
+    let knob_button = Button::new(pc1, ActiveLevel::Low, Lockout::new(Duration::ZERO));
 
     // initialize buzzer
     let buzzer = gpioc
@@ -50,18 +65,60 @@ fn main() -> ! {
         .into_push_pull_output(&mut gpioc.moder, &mut gpioc.otyper);
 
     // initialize leds
-    let leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
+    let mut leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
 
     // set up system timer using default settings of 8 MHz
     let hal_clocks = rcc.cfgr.freeze(&mut flash.acr);
-    let mut systick = Systick::new(cp.SYST, hal_clocks, 6).unwrap();
+    let mut systick = Systick::new(cp.SYST, hal_clocks, Duration::from_ms(6)).unwrap();
+    // report a hang that caused the last reset by lighting every LED for
+    // a second (semihosting would fault without a debugger attached)
+    if hung {
+        for led in leds.iter_mut() {
+            led.on();
+        }
+        systick.delay_ms(1000_u32);
+    }
 
-    // initialize the board state structure
-    let mut timer = SimpleTimer::new(knob_button, discovery_button, leds, buzzer, 15000);
+    // keep time in the SysTick exception so slow updates don't lose ticks
+    systick.enable_interrupt();
 
-    // update the board state each time the systick timer wraps.
+    // initialize the board state structure
+    let mut timer = SimpleTimer::new(
+        knob_button,
+        discovery_button,
+        leds,
+        buzzer,
+        Duration::from_secs(15),
+    );
+
+    // reset the board if the timer stops updating, rather than freezing
+    // with the buzzer possibly stuck on
+    let iwdg = IndependentWatchdog::start(dp.IWDG, Duration::from_ms(500));
+    let mut supervisor = Supervisor::new(iwdg, systick.now());
+    let timer_task = supervisor.register(Duration::from_ms(100)).unwrap();
+    interrupt::free(|cs| SUPERVISOR.borrow(cs).replace(Some(supervisor)));
+
+    // update the board state each time the systick timer wraps, sleeping
+    // in between
     loop {
-        timer.update(systick.now());
-        systick.wait_til_wrapped();
+        let now = systick.now();
+        timer.update(now);
+        interrupt::free(|cs| {
+            if let Some(supervisor) = SUPERVISOR.borrow(cs).borrow_mut().as_mut() {
+                supervisor.check_in(timer_task, now);
+            }
+        });
+        systick.sleep_til_wrapped();
     }
 }
+
+#[exception]
+fn SysTick() {
+    systick::tick();
+    let now = systick::now();
+    interrupt::free(|cs| {
+        if let Some(supervisor) = SUPERVISOR.borrow(cs).borrow_mut().as_mut() {
+            supervisor.update(now);
+        }
+    });
+}
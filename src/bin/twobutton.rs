@@ -13,17 +13,25 @@
 /// The button on the proto board beeps the buzzer
 extern crate panic_semihosting; // logs messages to the host stderr; requires a debugger
 
-use timer::button::Button;
+use core::pin::pin;
 
+use timer::button::{ActiveLevel, Button};
+use timer::debounce::Lockout;
+use timer::executor::{self, Executor};
 use timer::systick;
+use timer::time::Duration;
+use timer::Buzzer;
 
 use f3::hal::stm32f30x;
 
+use f3::hal::gpio::gpioa::PA0;
 use f3::hal::gpio::gpioc::PC1;
 use f3::hal::gpio::{Floating, Input};
 use f3::hal::prelude::*;
 use f3::led::Leds;
 
+use embedded_hal::digital::v2::InputPin;
+
 use cortex_m_rt::entry;
 
 #[entry]
@@ -37,7 +45,7 @@ fn main() -> ! {
 
     // set up system timer using default settings of 8 MHz
     let hal_clocks = rcc.cfgr.freeze(&mut flash.acr);
-    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, 20).unwrap();
+    let mut systick = systick::Systick::new(cp.SYST, hal_clocks, Duration::from_ms(20)).unwrap();
 
     // enable (power on) buttons
     let mut gpioa = dp.GPIOA.split(&mut rcc.ahb);
@@ -47,43 +55,58 @@ fn main() -> ! {
     let pa0 = gpioa
         .pa0
         .into_floating_input(&mut gpioa.moder, &mut gpioa.pupdr);
-    let mut discovery_button = Button::new(pa0, 0);
+    let discovery_button = Button::new(pa0, ActiveLevel::High, Lockout::new(Duration::ZERO));
 
     let pc1 = gpioc
         .pc1
         .into_floating_input(&mut gpioc.moder, &mut gpioc.pupdr);
-    let mut knob_button: Button<PC1<Input<Floating>>> = Button::new(pc1, 0);
+    let knob_button: Button<PC1<Input<Floating>>> =
+        Button::new(pc1, ActiveLevel::Low, Lockout::new(Duration::ZERO));
 
     // initialize buzzer
-    let mut buzzer = gpioc
+    let buzzer = gpioc
         .pc3
         .into_push_pull_output(&mut gpioc.moder, &mut gpioc.otyper);
 
     // initialize leds
-    let mut index = 0;
-    let mut leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
+    let leds = Leds::new(dp.GPIOE.split(&mut rcc.ahb));
+
+    // each button gets its own task, so one being held no longer stops the
+    // other from working
+    let beep = pin!(beep_while_held(knob_button, buzzer));
+    let step = pin!(step_leds(discovery_button, leds));
+
+    let mut executor: Executor<2> = Executor::new();
+    executor.spawn(beep).unwrap();
+    executor.spawn(step).unwrap();
+    executor.run(&mut systick)
+}
 
+// if the knob button is pressed, beep the buzzer till
+// the knob button is released
+async fn beep_while_held(mut button: Button<PC1<Input<Floating>>>, mut buzzer: Buzzer) {
     loop {
-        // if the knob button is pressed, beep the buzzer till
-        // the knob button is released
-        if knob_button.update(systick.now()).is_pressed() {
-            buzzer.set_high();
-            while knob_button.update(systick.now()).is_pressed() {
-                systick.wait_til_wrapped();
-            }
-            buzzer.set_low();
-        }
+        until(&mut button, true).await;
+        buzzer.set_high();
+        until(&mut button, false).await;
+        buzzer.set_low();
+    }
+}
 
-        // change the state of the next led if user button pressed
-        // then wate till the button is released
-        if discovery_button.update(systick.now()).is_pressed() {
-            index = update_leds(&mut leds, index);
-            while discovery_button.update(systick.now()).is_pressed() {
-                systick.wait_til_wrapped();
-            }
-        } else {
-            systick.wait_til_wrapped();
-        }
+// change the state of the next led each time the user button is pressed
+async fn step_leds(mut button: Button<PA0<Input<Floating>>>, mut leds: Leds) {
+    let mut index = 0;
+    loop {
+        until(&mut button, true).await;
+        index = update_leds(&mut leds, index);
+        until(&mut button, false).await;
+    }
+}
+
+// wait, tick by tick, until the button is (or isn't) pressed
+async fn until<PIN: InputPin>(button: &mut Button<PIN>, pressed: bool) {
+    while button.update(executor::now()).is_pressed() != pressed {
+        executor::next_tick().await;
     }
 }
 
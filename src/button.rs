@@ -3,70 +3,121 @@
 // Please see the file LICENSE in the source
 // distribution of this software for license terms.
 
-use super::Milliseconds;
-// use f3::hal::gpio::gpioa::PA0;
-// use f3::hal::gpio::gpioc::PC1;
-use f3::hal::gpio::{Floating, Input};
-use f3::hal::prelude::*;
-
-//TODO: Consult w/ industry consultant on appropriate value
-/// Delay separating two single presses from a double-press
-const PRESS_BREAK: Milliseconds = 250;
-
-//TODO: Consult w/ industry consultant on appropriate value
-/// Delay until a hold is released
-const HOLD_BREAK: Milliseconds = 100;
-
-//TODO: Consult w/ industry consultant on appropriate value
-/// Delay separating a press from a hold
-const HOLD_DELAY: Milliseconds = 750;
-
-//TODO: Consult w/ industry consultant on appropriate value
-/// Time to wait after state change to ignore switch bounce
-const DEBOUNCE_DELAY: Milliseconds = 50;
-
-/// Makes the `Buttons` enum to avoid a need for trait objects
-macro_rules! button_pins {
-    {$($gpiox:ident $PIN:ident $pin:ident $mode:ident $hilo:ident $debounce:literal);+}    => {$(
-            use f3::hal::gpio::$gpiox::$PIN;
-            impl PushButton for $PIN<Input<$mode>> {
-                fn is_pressed(&self) -> bool {
-                    #[allow(deprecated)]
-                    self.$hilo()
-                }
-            }
-            )+
-        pub enum Buttons {
-            $(
-                $PIN(Button<$PIN<Input<$mode>>>),
-                )+
-        }
-        impl Buttons {
-            pub fn update(&mut self, now: Milliseconds) -> ButtonEvent {
-                match self {
-                    $(Buttons::$PIN(b)   => b.update(now),)+
-                }
-            }
-            $(pub fn $pin(btn: $PIN<Input<$mode>>, debounce: Milliseconds) -> Buttons {
-                Buttons::$PIN(Button::<$PIN<Input<$mode>>>::new(btn, debounce))
-            })+
-        }
+use super::debounce::{Debouncer, Lockout};
+use super::error::Full;
+use super::exti::Edge;
+use super::time::{Duration, Instant};
+
+use embedded_hal::digital::v2::InputPin;
+
+/// How long a `FancyButton` waits before deciding what the user is doing.
+///
+/// Slower timings give users who can't press quickly or precisely (motor
+/// impairments, gloves, etc.) more time between the presses of a
+/// multi-press, at the cost of a laggier response.
+///
+/// Debouncing isn't part of a `Timing`: each button is given its own
+/// `Debouncer` to suit its switch, so pick one to go with the preset (see
+/// `Timing::ACCESSIBLE`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Timing {
+    /// Delay separating two single presses from a double-press
+    pub press_break: Duration,
+    /// Delay separating a press from a hold
+    pub hold_delay: Duration,
+    /// How often to report that a hold is still going
+    pub hold_repeat: Duration,
+    /// How long a hold lasts before it's a long hold
+    pub long_hold: Duration,
+    /// How long a hold lasts before it's a very long hold
+    pub very_long_hold: Duration,
+}
+
+impl Timing {
+    /// Quick to respond, for practised users
+    pub const SNAPPY: Timing = Timing {
+        press_break: Duration::from_ms(150),
+        hold_delay: Duration::from_ms(450),
+        hold_repeat: Duration::from_ms(100),
+        long_hold: Duration::from_secs(2),
+        very_long_hold: Duration::from_secs(6),
+    };
+
+    //TODO: Consult w/ industry consultant on appropriate values
+    /// Suits most users
+    pub const DEFAULT: Timing = Timing {
+        press_break: Duration::from_ms(250),
+        hold_delay: Duration::from_ms(750),
+        hold_repeat: Duration::from_ms(250),
+        long_hold: Duration::from_secs(3),
+        very_long_hold: Duration::from_secs(8),
+    };
+
+    /// Long multi-press windows and holds, for users who need more time.
+    ///
+    /// A press from an unsteady hand can waver, so pair it with a longer
+    /// lockout than the usual 50 ms to keep one press from counting as
+    /// several:
+    ///
+    /// ```
+    /// use timer::button::{ActiveLevel, FancyButton, Timing};
+    /// use timer::debounce::Lockout;
+    /// use timer::time::Duration;
+    /// # fn make<P: embedded_hal::digital::v2::InputPin>(pin: P) {
+    ///
+    /// let button = FancyButton::new(
+    ///     pin,
+    ///     ActiveLevel::Low,
+    ///     Timing::ACCESSIBLE,
+    ///     Lockout::new(Duration::from_ms(80)),
+    /// );
+    /// # }
+    /// ```
+    pub const ACCESSIBLE: Timing = Timing {
+        press_break: Duration::from_ms(600),
+        hold_delay: Duration::from_ms(1500),
+        hold_repeat: Duration::from_ms(500),
+        long_hold: Duration::from_secs(4),
+        very_long_hold: Duration::from_secs(10),
     };
 }
 
-/// A button that can be pressed or not.
-pub trait PushButton {
-    fn is_pressed(&self) -> bool;
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing::DEFAULT
+    }
+}
+
+/// Which level a button's pin reads while the button is pressed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActiveLevel {
+    /// Pressed reads high, like the discovery board user button (PA0)
+    High,
+    /// Pressed reads low, like a button that pulls its pin to ground (PC1
+    /// on the proto board)
+    Low,
 }
 
-// implement PushButton for both buttons used in this application
-button_pins! {
-    gpioa PA0 pa0 Floating is_high 20;
-    gpioc PC1 pc1 Floating is_low 0
+impl ActiveLevel {
+    /// Read whether the button on `pin` is pressed.
+    pub fn is_pressed<PIN: InputPin>(self, pin: &PIN) -> Result<bool, PIN::Error> {
+        match self {
+            ActiveLevel::High => pin.is_high(),
+            ActiveLevel::Low => pin.is_low(),
+        }
+    }
+
+    /// Whether a pin at `high` (or low) means the button is pressed.
+    pub fn pressed_when(self, high: bool) -> bool {
+        match self {
+            ActiveLevel::High => high,
+            ActiveLevel::Low => !high,
+        }
+    }
 }
 
 /// Represents a button event.
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ButtonEvent {
     /// Button pressed
     Push,
@@ -98,187 +149,663 @@ impl ButtonEvent {
     }
 }
 
-/// A struct to represent an (optionally) debounced button inside a clocked
-/// loop.
-pub struct Button<BTN> {
+/// A struct to represent a debounced button inside a clocked loop.
+pub struct Button<PIN, D = Lockout> {
     last_state: ButtonEvent,
-    debounce_delay: Option<Milliseconds>,
-    debouncing_till: Option<Milliseconds>,
-    button: BTN,
+    /// The last reading, before debouncing
+    raw: bool,
+    debouncer: D,
+    active: ActiveLevel,
+    pin: PIN,
 }
 
-impl<BTN: PushButton> Button<BTN> {
+impl<PIN: InputPin, D: Debouncer> Button<PIN, D> {
     /// Create a new Button.
     ///
     /// # Params
-    /// * `button` - The button this is representing.
-    /// * `debounce` - The amount of time to ignore the button after a state
-    /// change. Set to 0 (or less, if `Milliseconds` is signed) to disable
-    /// debouncing.
-    pub fn new(button: BTN, debounce: Milliseconds) -> Button<BTN> {
+    /// * `pin` - The pin the button is on.
+    /// * `active` - The level `pin` reads while the button is pressed.
+    /// * `debouncer` - How to filter out switch bounce, e.g.
+    ///   `Lockout::new(Duration::from_ms(20))`. `Lockout::new(Duration::ZERO)`
+    ///   disables debouncing.
+    pub fn new(pin: PIN, active: ActiveLevel, debouncer: D) -> Button<PIN, D> {
         use ButtonEvent::*;
         // let state = if button.is_pressed() { Pressed } else { NotPressed };
         Button {
             last_state: NotPressed,
-            // in case Milliseconds is changed to signed type
-            #[allow(clippy::absurd_extreme_comparisons)]
-            debounce_delay: if 0 >= debounce { None } else { Some(debounce) },
-            debouncing_till: None,
-            button,
+            raw: false,
+            debouncer,
+            active,
+            pin,
         }
     }
 
-    /// Check the button state (if not debouncing) and return the current
-    /// state as a `ButtonEvent`.
+    /// Check the button state and return the current (debounced) state as
+    /// a `ButtonEvent`. If the pin can't be read, the button is taken to be
+    /// unchanged.
     ///
     /// # Params
-    /// * `now` - The current time in milliseconds. Note that this **must**
-    /// be a reasonably accurate representation of the actual time for the
-    /// debouncing to work as expected.
-    pub fn update(&mut self, now: Milliseconds) -> ButtonEvent {
-        use ButtonEvent::*;
-        if self.debounce(now) {
-            return self.last_state;
-        }
+    /// * `now` - The current time. Note that this **must** be a reasonably
+    ///   accurate representation of the actual time for time-based
+    ///   debouncers to work as expected.
+    pub fn update(&mut self, now: Instant) -> ButtonEvent {
+        let pressed = self.active.is_pressed(&self.pin).unwrap_or(self.raw);
+        self.step(now, pressed)
+    }
+
+    /// Like `update`, but returns the error if the pin can't be read.
+    pub fn try_update(&mut self, now: Instant) -> Result<ButtonEvent, PIN::Error> {
+        let pressed = self.active.is_pressed(&self.pin)?;
+        Ok(self.step(now, pressed))
+    }
 
-        match (self.last_state.is_pressed(), self.button.is_pressed()) {
+    /// Take an edge timestamped by an EXTI interrupt handler, instead of
+    /// reading the pin. Feed edges oldest first, then call `advance`.
+    ///
+    /// Time-based debouncers (`Lockout`, `Asymmetric`) suit edges;
+    /// sample-counting ones expect regular readings.
+    pub fn feed(&mut self, edge: Edge) -> ButtonEvent {
+        self.step(edge.at, self.active.pressed_when(edge.high))
+    }
+
+    /// Move on to `now` without reading the pin, taking it to be where the
+    /// last edge left it. Lets time-based debouncers settle between edges.
+    pub fn advance(&mut self, now: Instant) -> ButtonEvent {
+        self.step(now, self.raw)
+    }
+
+    /// Release the pin.
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+
+    /// Move on to `now`, with the button `pressed` or not before
+    /// debouncing.
+    fn step(&mut self, now: Instant, pressed: bool) -> ButtonEvent {
+        use ButtonEvent::*;
+        self.raw = pressed;
+        match (
+            self.last_state.is_pressed(),
+            self.debouncer.update(now, pressed),
+        ) {
             // if button was pressed and is still pressed
             (true, true) => Pressed,
             // if button was not pressed and is still not pressed
             (false, false) => NotPressed,
             // if button was not pressed and now is
             (false, true) => {
-                // set debounce delay
-                self.set_debounce(now);
                 self.last_state = Pressed;
                 Push
             }
             // if button was pressed and now is not
             (true, false) => {
-                self.set_debounce(now);
                 self.last_state = NotPressed;
                 Release
             }
         }
     }
-
-    /// Convenience function. Sets self to return last button state without
-    /// polling the button until `now + DEBOUNCE_DELAY`.
-    fn set_debounce(&mut self, now: Milliseconds) {
-        self.debouncing_till = self.debounce_delay.map(|d| now + d);
-    }
-
-    /// Handles debounce delay.
-    ///
-    /// # Returns
-    /// `bool` - `true` if we're waiting for debounce period
-    fn debounce(&mut self, now: Milliseconds) -> bool {
-        match self.debouncing_till {
-            None => false,
-            Some(s) if s < now => true,
-            Some(_) => {
-                self.debouncing_till = None;
-                false
-            }
-        }
-    }
 }
 
 /// Represents a fancy button event.
-#[derive(PartialEq)]
+///
+/// A run of presses ends in either `Press(n)`, once the button has been
+/// left alone for `press_break`, or a hold. A hold goes `HoldStart(n)`,
+/// then `Holding(..)` every `hold_repeat` (with `LongHold` and
+/// `VeryLongHold` as it passes those thresholds), and finally
+/// `Release(..)`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MultiButtonEvent {
     /// Button pressed. u8 is number of presses (double, triple, etc.)
     Press(u8),
-    /// Button being held. u8 is preceding number of presses (v^,v^^^^^
-    /// would be Hold(1)).
-    Hold(u8),
-    /// Button hold released.
-    Release,
+    /// Button has been held for `hold_delay`. u8 is preceding number of
+    /// presses (v^,v^^^^^ would be HoldStart(1)).
+    HoldStart(u8),
+    /// Button still being held. Duration is how long so far.
+    Holding(Duration),
+    /// Button has been held for `long_hold`.
+    LongHold,
+    /// Button has been held for `very_long_hold`.
+    VeryLongHold,
+    /// Button hold released. Duration is how long it was held in total.
+    Release(Duration),
 }
 
-pub struct FancyButton<BTN> {
+/// How far a hold has got.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum HoldTier {
+    /// Not held long enough to be a hold
+    None,
+    /// Past `hold_delay`
+    Hold,
+    /// Past `long_hold`
+    Long,
+    /// Past `very_long_hold`
+    VeryLong,
+}
+
+/// A button that recognizes multi-presses and holds inside a clocked loop.
+pub struct FancyButton<PIN, D = Lockout> {
     last_state: bool, // true if pressed
-    last_change_time: Milliseconds,
-    debouncing_till: Option<Milliseconds>,
+    last_change_time: Instant,
+    /// The last reading, before debouncing
+    raw: bool,
+    debouncer: D,
+    /// Presses so far in the current run
     prev_presses: u8,
-    holding: bool,
-    button: BTN,
+    /// How far the current hold has got
+    hold_tier: HoldTier,
+    /// When next to report `Holding`
+    next_holding: Instant,
+    /// An event held back to go out on the next step
+    queued: Option<MultiButtonEvent>,
+    timing: Timing,
+    active: ActiveLevel,
+    pin: PIN,
 }
 
-impl<BTN: PushButton> FancyButton<BTN> {
-    pub fn new(button: BTN) -> FancyButton<BTN> {
+impl<PIN: InputPin, D: Debouncer> FancyButton<PIN, D> {
+    /// Create a new FancyButton.
+    ///
+    /// # Params
+    /// * `pin` - The pin the button is on.
+    /// * `active` - The level `pin` reads while the button is pressed.
+    /// * `timing` - How long to wait before deciding what the user is
+    ///   doing, e.g. `Timing::DEFAULT`.
+    /// * `debouncer` - How to filter out switch bounce, e.g.
+    ///   `Lockout::new(Duration::from_ms(50))`.
+    pub fn new(pin: PIN, active: ActiveLevel, timing: Timing, debouncer: D) -> FancyButton<PIN, D> {
         FancyButton {
             last_state: false,
-            last_change_time: 0,
-            debouncing_till: None,
+            last_change_time: Instant::default(),
+            raw: false,
+            debouncer,
             prev_presses: 0,
-            holding: false,
-            button,
+            hold_tier: HoldTier::None,
+            next_holding: Instant::default(),
+            queued: None,
+            timing,
+            active,
+            pin,
         }
     }
 
-    pub fn update(&mut self, now: Milliseconds) -> Option<MultiButtonEvent> {
-        if let Some(s) = self.debouncing_till {
-            if s < now {
-                return None;
-            } else {
-                self.debouncing_till = None;
-            }
-        }
-        let current_state = self.button.is_pressed();
+    /// The timing currently in use.
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Change the timing, e.g. from a settings menu. Takes effect from the
+    /// next `update`.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// `true` if the (debounced) button is currently pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.last_state
+    }
+
+    /// Check the button and return any event that's completed. If the pin
+    /// can't be read, the button is taken to be unchanged.
+    ///
+    /// At most one event is returned per call; if several become due at
+    /// once, the rest follow on later calls.
+    pub fn update(&mut self, now: Instant) -> Option<MultiButtonEvent> {
+        let pressed = self.active.is_pressed(&self.pin).unwrap_or(self.raw);
+        self.step(now, pressed)
+    }
+
+    /// Like `update`, but returns the error if the pin can't be read.
+    pub fn try_update(&mut self, now: Instant) -> Result<Option<MultiButtonEvent>, PIN::Error> {
+        let pressed = self.active.is_pressed(&self.pin)?;
+        Ok(self.step(now, pressed))
+    }
+
+    /// Take an edge timestamped by an EXTI interrupt handler, instead of
+    /// reading the pin, so press timing is accurate to the edge rather
+    /// than the tick. Feed edges oldest first, then call `advance` so
+    /// presses and holds still complete while the pin is quiet.
+    ///
+    /// Edges fed back to back, with no `advance` between them, still come
+    /// out as the right presses and holds, but not every event in between
+    /// is reported: a hold that ends within the batch gives `HoldStart`,
+    /// then `Release` on the next call, without the `Holding`, `LongHold`
+    /// or `VeryLongHold` it passed on the way.
+    pub fn feed(&mut self, edge: Edge) -> Option<MultiButtonEvent> {
+        self.step(edge.at, self.active.pressed_when(edge.high))
+    }
+
+    /// Move on to `now` without reading the pin, taking it to be where the
+    /// last edge left it.
+    pub fn advance(&mut self, now: Instant) -> Option<MultiButtonEvent> {
+        self.step(now, self.raw)
+    }
+
+    /// Release the pin.
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+
+    /// Move on to `now`, with the button `pressed` or not before
+    /// debouncing.
+    fn step(&mut self, now: Instant, pressed: bool) -> Option<MultiButtonEvent> {
+        use MultiButtonEvent::*;
+        self.raw = pressed;
+        let current_state = self.debouncer.update(now, pressed);
+        // how long the button has been in its current state
         let duration = now - self.last_change_time;
+        // an event held back by the last step goes out first
+        let earlier = self.queued.take();
 
-        if current_state {
-            // button is pressed
-            if self.last_state {
-                // button is *still* pressed
-                if duration >= HOLD_DELAY {
-                    // button has been pressed long enough to count as being
-                    // held
-                    self.holding = true;
-                    Some(MultiButtonEvent::Hold(self.prev_presses))
+        let event = match (self.last_state, current_state) {
+            // button has just been pressed
+            (false, true) => {
+                self.last_state = true;
+                self.last_change_time = now;
+                // with edges fed back to back, the last run of presses
+                // may have ended without a step in between to say so
+                if 0 < self.prev_presses && duration >= self.timing.press_break {
+                    let presses = self.prev_presses;
+                    self.prev_presses = 0;
+                    Some(Press(presses))
                 } else {
                     None
                 }
-            } else {
-                // !self.last_state
-                if duration < PRESS_BREAK {
-                    self.prev_presses += 1;
-                }
-                self.last_state = true;
-                self.last_change_time = now;
-                None
-            } // fi self.last_state
-        } else {
-            // !current_state
-            if self.last_state {
+            }
+            // button is *still* pressed
+            (true, true) => self.hold(now, duration),
+            // button has just been released
+            (true, false) => {
                 self.last_state = false;
                 self.last_change_time = now;
-                self.debouncing_till = Some(now + DEBOUNCE_DELAY);
-                None
-            } else {
-                // !self.last_state
-                if self.holding {
-                    if duration >= HOLD_BREAK {
-                        self.holding = false;
-                        self.prev_presses = 0;
-                        // Some(MultiButtonEvent::Release)
-                        None
-                    } else {
-                        // None
-                        Some(MultiButtonEvent::Hold(self.prev_presses))
-                    }
+                if HoldTier::None == self.hold_tier && duration < self.timing.hold_delay {
+                    // a press; wait to see if another follows
+                    self.prev_presses = self.prev_presses.saturating_add(1);
+                    None
+                } else if HoldTier::None == self.hold_tier {
+                    // a hold that no step saw start (edges fed back to
+                    // back), so start and end it now
+                    let presses = self.prev_presses;
+                    self.prev_presses = 0;
+                    self.queued = Some(Release(duration));
+                    Some(HoldStart(presses))
+                } else {
+                    self.hold_tier = HoldTier::None;
+                    self.prev_presses = 0;
+                    Some(Release(duration))
+                }
+            }
+            // button is still not pressed; if it's been long enough, the
+            // run of presses is over
+            (false, false) => {
+                if 0 < self.prev_presses && duration >= self.timing.press_break {
+                    let presses = self.prev_presses;
+                    self.prev_presses = 0;
+                    Some(Press(presses))
                 } else {
-                    // !self.holding
-                    if duration >= PRESS_BREAK {
-                        let presses = self.prev_presses + 1;
-                        self.prev_presses = 0;
-                        Some(MultiButtonEvent::Press(presses))
-                    } else {
-                        None
-                    }
-                } // fi self.holding
-            } // fi self.last_state
-        } // fi current_state
-    } // end fn Button.update
-} // end impl<BTN: PushButton> Button<BTN>
+                    None
+                }
+            }
+        };
+
+        match earlier {
+            Some(earlier) => {
+                self.queued = event;
+                Some(earlier)
+            }
+            None => event,
+        }
+    } // end fn FancyButton.step
+
+    /// Move a hold along, `held` after the button was pressed.
+    fn hold(&mut self, now: Instant, held: Duration) -> Option<MultiButtonEvent> {
+        use MultiButtonEvent::*;
+        match self.hold_tier {
+            HoldTier::None if held >= self.timing.hold_delay => {
+                self.hold_tier = HoldTier::Hold;
+                self.next_holding = now + self.timing.hold_repeat;
+                Some(HoldStart(self.prev_presses))
+            }
+            HoldTier::None => None,
+            HoldTier::Hold if held >= self.timing.long_hold => {
+                self.hold_tier = HoldTier::Long;
+                Some(LongHold)
+            }
+            HoldTier::Long if held >= self.timing.very_long_hold => {
+                self.hold_tier = HoldTier::VeryLong;
+                Some(VeryLongHold)
+            }
+            _ if now >= self.next_holding => {
+                self.next_holding += self.timing.hold_repeat;
+                Some(Holding(held))
+            }
+            _ => None,
+        }
+    }
+} // end impl<PIN: InputPin, D: Debouncer> FancyButton<PIN, D>
+
+/// Identifies where an `InputEvent` came from: the index of its source in
+/// the slice given to `EventBus::collect`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourceId(pub u8);
+
+/// What an input source did.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputKind {
+    /// A `Button` was pushed or released
+    Button(ButtonEvent),
+    /// A `FancyButton` finished an event
+    Multi(MultiButtonEvent),
+    /// An encoder moved this many steps, negative for counter-clockwise
+    Encoder(i32),
+}
+
+/// Represents something that happened on an input, and when.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InputEvent {
+    /// Which input it happened on
+    pub source: SourceId,
+    /// When it was collected
+    pub at: Instant,
+    /// What happened
+    pub kind: InputKind,
+}
+
+/// Something that produces input events for an `EventBus` to collect.
+pub trait InputSource {
+    /// Check the input at `now`, and return what happened, if anything
+    /// worth reporting.
+    fn poll_input(&mut self, now: Instant) -> Option<InputKind>;
+}
+
+impl<PIN: InputPin, D: Debouncer> InputSource for Button<PIN, D> {
+    /// Reports pushes and releases, not the steady states in between.
+    fn poll_input(&mut self, now: Instant) -> Option<InputKind> {
+        let event = self.update(now);
+        if event.is_change() {
+            Some(InputKind::Button(event))
+        } else {
+            None
+        }
+    }
+}
+
+impl<PIN: InputPin, D: Debouncer> InputSource for FancyButton<PIN, D> {
+    fn poll_input(&mut self, now: Instant) -> Option<InputKind> {
+        self.update(now).map(InputKind::Multi)
+    }
+}
+
+/// A fixed-capacity queue of input events from any number of buttons (and
+/// encoders), for application code to drain in order without knowing
+/// which inputs exist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::button::{ButtonEvent, EventBus, InputKind, InputSource};
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run(mut systick: Systick, a: &mut dyn InputSource, b: &mut dyn InputSource) -> ! {
+///
+/// let mut bus: EventBus<8> = EventBus::new();
+///
+/// loop {
+///     bus.collect(systick.now(), &mut [&mut *a, &mut *b]);
+///     while let Some(event) = bus.poll() {
+///         if let InputKind::Button(ButtonEvent::Push) = event.kind {
+///             // react to a push on button `event.source`
+///         }
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct EventBus<const N: usize> {
+    events: [Option<InputEvent>; N],
+    /// Where the oldest event is
+    head: usize,
+    /// How many events are waiting
+    len: usize,
+    /// How many events didn't fit
+    dropped: u32,
+}
+
+impl<const N: usize> EventBus<N> {
+    /// Create an empty `EventBus`.
+    pub fn new() -> Self {
+        EventBus {
+            events: [None; N],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Check every source at `now` and queue what they report. Each source
+    /// is identified by its index in `sources`.
+    pub fn collect(&mut self, now: Instant, sources: &mut [&mut dyn InputSource]) {
+        for (idx, source) in sources.iter_mut().enumerate() {
+            if let Some(kind) = source.poll_input(now) {
+                let event = InputEvent {
+                    source: SourceId(idx as u8),
+                    at: now,
+                    kind,
+                };
+                // counted in `dropped` if it doesn't fit
+                self.push(event).ok();
+            }
+        }
+    }
+
+    /// Queue an event from a source `collect` doesn't handle, e.g. a
+    /// button fed from an EXTI edge queue.
+    pub fn push(&mut self, event: InputEvent) -> Result<(), Full> {
+        if self.len == N {
+            self.dropped = self.dropped.saturating_add(1);
+            return Err(Full);
+        }
+        self.events[(self.head + self.len) % N] = Some(event);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Take the oldest event. Call repeatedly until it returns `None` to
+    /// drain all of them.
+    pub fn poll(&mut self) -> Option<InputEvent> {
+        if 0 == self.len {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        event
+    }
+
+    /// `true` if there are no events waiting.
+    pub fn is_empty(&self) -> bool {
+        0 == self.len
+    }
+
+    /// How many events have been lost because the queue was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<const N: usize> Default for EventBus<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debounce::NoDebounce;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use MultiButtonEvent::*;
+
+    /// A pin that's never read, for buttons fed edges instead.
+    struct NoPin;
+
+    impl InputPin for NoPin {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(false)
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(true)
+        }
+    }
+
+    fn button() -> FancyButton<NoPin, NoDebounce> {
+        FancyButton::new(
+            NoPin,
+            ActiveLevel::High,
+            Timing::DEFAULT,
+            NoDebounce::default(),
+        )
+    }
+
+    /// A pin that reads whatever it's set to.
+    struct Pin<'a>(&'a Cell<bool>);
+
+    impl InputPin for Pin<'_> {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.get())
+        }
+    }
+
+    /// Polls a button every ms up to `until`, with it held down from each
+    /// start to each end in `held`, returning the events and when they came.
+    fn poll(held: &[(u32, u32)], until: u32) -> Vec<(u32, MultiButtonEvent)> {
+        let level = Cell::new(false);
+        let mut button = FancyButton::new(
+            Pin(&level),
+            ActiveLevel::High,
+            Timing::DEFAULT,
+            NoDebounce::default(),
+        );
+        (0..until)
+            .filter_map(|ms| {
+                level.set(held.iter().any(|&(from, to)| from <= ms && ms < to));
+                button.update(Instant::from_ms(ms)).map(|e| (ms, e))
+            })
+            .collect()
+    }
+
+    fn edge(ms: u32, high: bool) -> Edge {
+        Edge {
+            at: Instant::from_ms(ms),
+            high,
+        }
+    }
+
+    #[test]
+    fn batched_clicks_stay_apart() {
+        let mut button = button();
+        let events: Vec<_> = [
+            edge(1000, true),
+            edge(1050, false),
+            edge(2000, true),
+            edge(2050, false),
+        ]
+        .iter()
+        .map(|&e| button.feed(e))
+        .collect();
+        assert_eq!(vec![None, None, Some(Press(1)), None], events);
+        assert_eq!(Some(Press(1)), button.advance(Instant::from_ms(2300)));
+        assert_eq!(None, button.advance(Instant::from_ms(2400)));
+    }
+
+    #[test]
+    fn batched_double_press() {
+        let mut button = button();
+        for &e in &[
+            edge(1000, true),
+            edge(1050, false),
+            edge(1200, true),
+            edge(1250, false),
+        ] {
+            assert_eq!(None, button.feed(e));
+        }
+        assert_eq!(None, button.advance(Instant::from_ms(1400)));
+        assert_eq!(Some(Press(2)), button.advance(Instant::from_ms(1500)));
+    }
+
+    #[test]
+    fn batched_hold_is_still_a_hold() {
+        let mut button = button();
+        assert_eq!(None, button.feed(edge(1000, true)));
+        assert_eq!(None, button.feed(edge(1100, false)));
+        assert_eq!(None, button.feed(edge(1200, true)));
+        assert_eq!(Some(HoldStart(1)), button.feed(edge(2200, false)));
+        assert_eq!(
+            Some(Release(Duration::from_ms(1000))),
+            button.advance(Instant::from_ms(2300))
+        );
+        assert_eq!(None, button.advance(Instant::from_ms(3000)));
+    }
+
+    #[test]
+    fn hold_lifecycle() {
+        let events = poll(&[(100, 9100)], 9200);
+        let at = |wanted: fn(&MultiButtonEvent) -> bool| -> Vec<u32> {
+            events
+                .iter()
+                .filter(|(_, e)| wanted(e))
+                .map(|&(ms, _)| ms)
+                .collect()
+        };
+
+        // hold_delay after the push
+        assert_eq!((850, HoldStart(0)), events[0]);
+        // then every hold_repeat, saying how long so far
+        assert_eq!((1100, Holding(Duration::from_ms(1000))), events[1]);
+        assert_eq!((1350, Holding(Duration::from_ms(1250))), events[2]);
+        assert_eq!((1600, Holding(Duration::from_ms(1500))), events[3]);
+        // each tier once, at its threshold
+        assert_eq!(vec![3100], at(|e| *e == LongHold));
+        assert_eq!(vec![8100], at(|e| *e == VeryLongHold));
+        // no press, and the release carries the whole hold
+        assert!(at(|e| matches!(e, Press(_))).is_empty());
+        assert_eq!(
+            Some(&(9100, Release(Duration::from_ms(9000)))),
+            events.last()
+        );
+        // holding keeps to the hold_repeat cadence throughout, at most a
+        // tick late when a tier takes its turn
+        let holding = at(|e| matches!(e, Holding(_)));
+        assert!(holding.iter().all(|ms| (ms - 850) % 250 <= 1));
+        assert_eq!(32, holding.len());
+    }
+
+    #[test]
+    fn click_then_hold() {
+        let events = poll(&[(100, 200), (300, 1500)], 1600);
+        assert_eq!((1050, HoldStart(1)), events[0]);
+        assert_eq!(
+            Some(&(1500, Release(Duration::from_ms(1200)))),
+            events.last()
+        );
+    }
+
+    #[test]
+    fn polled_presses() {
+        assert_eq!(vec![(450, Press(1))], poll(&[(100, 200)], 1000));
+        assert_eq!(vec![(650, Press(2))], poll(&[(100, 200), (300, 400)], 1000));
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::button::ButtonEvent;
+use super::time::{Duration, Instant};
+
+/// The most buttons a `Chords` can watch
+pub const MAX_BUTTONS: usize = 8;
+
+/// How many events can wait to be collected
+const QUEUE_LEN: usize = MAX_BUTTONS + 1;
+
+/// Represents what a group of buttons did.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChordEvent {
+    /// Button `usize` was pushed on its own
+    Push(usize),
+    /// Button `usize`, which was pushed on its own, was released
+    Release(usize),
+    /// The buttons in the mask (bit `n` for button `n`) were pushed
+    /// together
+    Together(u8),
+    /// Button `clicked` was pushed and released while button `held` was
+    /// held down
+    HoldClick { held: usize, clicked: usize },
+}
+
+/// Recognizes buttons pushed in combination, given the `ButtonEvent`s of
+/// `N` buttons.
+///
+/// Buttons pushed within `window` of each other make a chord, reported as
+/// `Together` instead of their individual `Push`es and `Release`s. A
+/// button pushed on its own is only reported once `window` has passed (or
+/// it has been released), since until then it might still become part of a
+/// chord. Once a lone button is down, clicks of the other buttons are
+/// reported as `HoldClick` instead of their individual events; the held
+/// button's own `Push` has already been reported by then.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::chord::{ChordEvent, Chords};
+/// use timer::time::Duration;
+/// # use timer::button::Button;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run<A, B>(mut systick: Systick, mut a: Button<A>, mut b: Button<B>) -> !
+/// # where
+/// #     A: embedded_hal::digital::v2::InputPin,
+/// #     B: embedded_hal::digital::v2::InputPin,
+/// # {
+///
+/// let mut chords: Chords<2> = Chords::new(Duration::from_ms(80));
+///
+/// loop {
+///     let now = systick.now();
+///     chords.update(now, [a.update(now), b.update(now)]);
+///     while let Some(event) = chords.poll() {
+///         match event {
+///             ChordEvent::Together(0b11) => { /* toggle the child lock */ }
+///             ChordEvent::Push(0) => { /* button a on its own */ }
+///             _ => (),
+///         }
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Chords<const N: usize> {
+    /// How close together pushes must be to make a chord
+    window: Duration,
+    /// The buttons that are down
+    down: u8,
+    /// When the first button of a possible chord was pushed
+    started: Option<Instant>,
+    /// The buttons pushed since `started`
+    gathered: u8,
+    /// The button that was pushed on its own, while it's down
+    single: Option<usize>,
+    /// Buttons that are down but whose events are being swallowed
+    swallowed: u8,
+    /// Events waiting to be collected, oldest first
+    queue: [Option<ChordEvent>; QUEUE_LEN],
+    /// How many events are waiting
+    queued: usize,
+}
+
+impl<const N: usize> Chords<N> {
+    /// Create a `Chords` that counts pushes within `window` of each other
+    /// as a chord.
+    ///
+    /// # Panics
+    /// Will panic if `N` is more than `MAX_BUTTONS`.
+    pub fn new(window: Duration) -> Self {
+        assert!(N <= MAX_BUTTONS, "too many buttons to watch");
+        Chords {
+            window,
+            down: 0,
+            started: None,
+            gathered: 0,
+            single: None,
+            swallowed: 0,
+            queue: [None; QUEUE_LEN],
+            queued: 0,
+        }
+    }
+
+    /// Take the latest event from each button, in order. Collect the
+    /// resulting events with `poll` before the next `update`.
+    pub fn update(&mut self, now: Instant, events: [ButtonEvent; N]) {
+        for (idx, event) in events.iter().enumerate() {
+            let bit = 1 << idx;
+            match event {
+                ButtonEvent::Push => {
+                    self.down |= bit;
+                    if self.started.is_some() {
+                        // joins the chord being gathered
+                        self.gathered |= bit;
+                    } else if self.single.is_some() || 0 != self.swallowed {
+                        // clicked while another is held, or pushed while a
+                        // chord is being let go of
+                        self.swallowed |= bit;
+                    } else {
+                        // might be the start of a chord
+                        self.started = Some(now);
+                        self.gathered = bit;
+                    }
+                }
+                ButtonEvent::Release => {
+                    self.down &= !bit;
+                    if 0 != self.swallowed & bit {
+                        self.swallowed &= !bit;
+                        if let Some(held) = self.single {
+                            self.push(ChordEvent::HoldClick { held, clicked: idx });
+                        }
+                    } else if Some(idx) == self.single {
+                        self.single = None;
+                        self.push(ChordEvent::Release(idx));
+                    }
+                    // a release from a chord being gathered settles it below
+                }
+                ButtonEvent::Pressed | ButtonEvent::NotPressed => (),
+            }
+        }
+
+        // settle the chord being gathered once the window has passed or
+        // one of its buttons has been let go of
+        if let Some(start) = self.started {
+            if now - start >= self.window || 0 != self.gathered & !self.down {
+                self.started = None;
+                self.settle();
+            }
+        }
+    }
+
+    /// Collect the oldest event. Call repeatedly until it returns `None` to
+    /// collect all of them.
+    pub fn poll(&mut self) -> Option<ChordEvent> {
+        if 0 == self.queued {
+            return None;
+        }
+        let event = self.queue[0].take();
+        self.queue.rotate_left(1);
+        self.queued -= 1;
+        event
+    }
+
+    /// Report what the gathered buttons turned out to be.
+    fn settle(&mut self) {
+        if 1 == self.gathered.count_ones() {
+            let idx = self.gathered.trailing_zeros() as usize;
+            self.push(ChordEvent::Push(idx));
+            if 0 != self.down & self.gathered {
+                self.single = Some(idx);
+            } else {
+                // a tap shorter than the window
+                self.push(ChordEvent::Release(idx));
+            }
+        } else {
+            self.push(ChordEvent::Together(self.gathered));
+            self.swallowed |= self.gathered & self.down;
+        }
+        self.gathered = 0;
+    }
+
+    /// Queue an event, dropping it if the queue is full (which only happens
+    /// if events aren't collected after every `update`).
+    fn push(&mut self, event: ChordEvent) {
+        if self.queued < QUEUE_LEN {
+            self.queue[self.queued] = Some(event);
+            self.queued += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a `Chords` every ms up to `until`, with each button held down
+    /// from each start to each end in its `held`, returning the events and
+    /// when they came.
+    fn run<const N: usize>(held: [&[(u32, u32)]; N], until: u32) -> Vec<(u32, ChordEvent)> {
+        let mut chords: Chords<N> = Chords::new(Duration::from_ms(80));
+        let mut was = [false; N];
+        let mut events = Vec::new();
+        for ms in 0..until {
+            let mut buttons = [ButtonEvent::NotPressed; N];
+            for (idx, button) in buttons.iter_mut().enumerate() {
+                let is = held[idx].iter().any(|&(from, to)| from <= ms && ms < to);
+                *button = match (was[idx], is) {
+                    (false, true) => ButtonEvent::Push,
+                    (true, true) => ButtonEvent::Pressed,
+                    (true, false) => ButtonEvent::Release,
+                    (false, false) => ButtonEvent::NotPressed,
+                };
+                was[idx] = is;
+            }
+            chords.update(Instant::from_ms(ms), buttons);
+            while let Some(event) = chords.poll() {
+                events.push((ms, event));
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn pushes_inside_the_window_are_a_chord() {
+        let events = run([&[(100, 500)], &[(150, 600)]], 700);
+        // settled when the window closes, with neither button reported alone
+        assert_eq!(vec![(180, ChordEvent::Together(0b11))], events);
+    }
+
+    #[test]
+    fn lone_push_waits_out_the_window() {
+        let events = run([&[(100, 500)], &[]], 700);
+        assert_eq!(
+            vec![(180, ChordEvent::Push(0)), (500, ChordEvent::Release(0))],
+            events
+        );
+    }
+
+    #[test]
+    fn click_while_held() {
+        let events = run([&[(100, 1000)], &[(400, 450)]], 1100);
+        assert_eq!(
+            vec![
+                (180, ChordEvent::Push(0)),
+                (
+                    450,
+                    ChordEvent::HoldClick {
+                        held: 0,
+                        clicked: 1
+                    }
+                ),
+                (1000, ChordEvent::Release(0)),
+            ],
+            events
+        );
+    }
+}
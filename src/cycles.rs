@@ -0,0 +1,180 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::{Microseconds, Nanoseconds};
+
+use cortex_m::peripheral::{DCB, DWT};
+
+use stm32f30x_hal::rcc::Clocks;
+
+/// The DWT cycle counter (CYCCNT) as a high-resolution clock.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::cycles::CycleClock;
+/// # fn run(mut cp: cortex_m::Peripherals, hal_clocks: stm32f30x_hal::rcc::Clocks) {
+///
+/// let clock = CycleClock::new(cp.DWT, &mut cp.DCB, hal_clocks);
+/// let mut stopwatch = clock.stopwatch();
+///
+/// stopwatch.start();
+/// // do something worth timing
+/// stopwatch.stop();
+/// let took = stopwatch.elapsed();
+/// # }
+/// ```
+pub struct CycleClock {
+    /// The data watchpoint and trace unit, kept so nothing else can
+    /// reconfigure the counter.
+    dwt: DWT,
+    /// The processor clock frequency, which the counter runs at
+    hz: u32,
+}
+
+impl CycleClock {
+    /// Enables and starts the cycle counter.
+    ///
+    /// # Arguments
+    ///
+    /// * `clocks` - The same clock frequencies given to `Systick::new`.
+    pub fn new(mut dwt: DWT, dcb: &mut DCB, clocks: Clocks) -> Self {
+        // the DWT is only powered when trace is enabled
+        dcb.enable_trace();
+        dwt.enable_cycle_counter();
+        CycleClock {
+            dwt,
+            hz: clocks.hclk().0,
+        }
+    }
+
+    /// Returns the raw cycle count. Wraps every 2^32 cycles (about a
+    /// minute at 72 MHz).
+    pub fn cycles(&self) -> u32 {
+        DWT::cycle_count()
+    }
+
+    /// Returns the counter frequency in Hz.
+    pub fn hz(&self) -> u32 {
+        self.hz
+    }
+
+    /// Converts a number of cycles to ns.
+    pub fn to_ns(&self, cycles: u64) -> Nanoseconds {
+        cycles_to(cycles, self.hz, 1_000_000_000)
+    }
+
+    /// Converts a number of cycles to µs.
+    pub fn to_us(&self, cycles: u64) -> Microseconds {
+        cycles_to(cycles, self.hz, 1_000_000)
+    }
+
+    /// Returns a new, stopped `Stopwatch` reading zero.
+    pub fn stopwatch(&self) -> Stopwatch {
+        Stopwatch {
+            hz: self.hz,
+            running: false,
+            last: 0,
+            elapsed: 0,
+            lap_start: 0,
+        }
+    }
+
+    /// Stops the cycle counter and gives back the DWT.
+    pub fn free(mut self) -> DWT {
+        self.dwt.disable_cycle_counter();
+        self.dwt
+    }
+}
+
+/// Converts `cycles` at `hz` to units of `1 / per_sec` seconds without
+/// overflowing for long spans.
+fn cycles_to(cycles: u64, hz: u32, per_sec: u64) -> u64 {
+    let hz = u64::from(hz);
+    cycles / hz * per_sec + cycles % hz * per_sec / hz
+}
+
+/// Measures time with the cycle counter. Made by `CycleClock::stopwatch`.
+///
+/// The counter wraps every 2^32 cycles, so while running, a `Stopwatch`
+/// must be read (with any of its methods) at least that often; it can
+/// accumulate any length of time as long as it is.
+pub struct Stopwatch {
+    /// The counter frequency in Hz
+    hz: u32,
+    /// `true` if started and not stopped since
+    running: bool,
+    /// The cycle count when `elapsed` was last brought up to date
+    last: u32,
+    /// Cycles counted while running
+    elapsed: u64,
+    /// The value of `elapsed` at the last lap
+    lap_start: u64,
+}
+
+impl Stopwatch {
+    /// Starts (or resumes) counting. Does nothing if already running.
+    pub fn start(&mut self) {
+        if !self.running {
+            self.last = DWT::cycle_count();
+            self.running = true;
+        }
+    }
+
+    /// Stops counting, keeping the time counted so far.
+    pub fn stop(&mut self) {
+        self.catch_up();
+        self.running = false;
+    }
+
+    /// Sets the time counted back to zero, without starting or stopping.
+    pub fn reset(&mut self) {
+        self.catch_up();
+        self.elapsed = 0;
+        self.lap_start = 0;
+    }
+
+    /// `true` if the stopwatch is counting
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Returns the number of cycles counted.
+    pub fn elapsed_cycles(&mut self) -> u64 {
+        self.catch_up();
+        self.elapsed
+    }
+
+    /// Returns the time counted, in ns.
+    pub fn elapsed(&mut self) -> Nanoseconds {
+        let cycles = self.elapsed_cycles();
+        cycles_to(cycles, self.hz, 1_000_000_000)
+    }
+
+    /// Returns the time counted, in µs.
+    pub fn elapsed_us(&mut self) -> Microseconds {
+        let cycles = self.elapsed_cycles();
+        cycles_to(cycles, self.hz, 1_000_000)
+    }
+
+    /// Returns the time counted since the last lap (or since the stopwatch
+    /// was created or reset), in ns, and starts a new lap.
+    pub fn lap(&mut self) -> Nanoseconds {
+        let now = self.elapsed_cycles();
+        let lap = now - self.lap_start;
+        self.lap_start = now;
+        cycles_to(lap, self.hz, 1_000_000_000)
+    }
+
+    /// Adds the cycles since the last reading to the total, if running.
+    fn catch_up(&mut self) {
+        if self.running {
+            let now = DWT::cycle_count();
+            // wrapping_sub copes with one wrap of the counter
+            self.elapsed += u64::from(now.wrapping_sub(self.last));
+            self.last = now;
+        }
+    }
+}
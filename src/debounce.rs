@@ -0,0 +1,385 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::time::{Duration, Instant};
+
+/// Filters switch bounce out of a stream of raw button readings.
+///
+/// Each `Button` or `FancyButton` owns one, so buttons with different
+/// switches can be debounced differently.
+pub trait Debouncer {
+    /// Take a raw reading (`true` if pressed) made at `now`, and return
+    /// whether the button should be considered pressed.
+    fn update(&mut self, now: Instant, raw: bool) -> bool;
+
+    /// Whether the button is currently considered pressed.
+    fn is_pressed(&self) -> bool;
+}
+
+/// Doesn't debounce at all: every reading is taken at face value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoDebounce {
+    state: bool,
+}
+
+impl Debouncer for NoDebounce {
+    fn update(&mut self, _now: Instant, raw: bool) -> bool {
+        self.state = raw;
+        raw
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// Accepts a change straight away, then ignores the switch for a while so
+/// it can settle.
+///
+/// Responds with no lag, but a glitch on the line is taken as a press.
+#[derive(Clone, Copy, Debug)]
+pub struct Lockout {
+    /// How long to ignore the switch after a change
+    delay: Duration,
+    state: bool,
+    /// When to start listening to the switch again
+    locked_until: Option<Instant>,
+}
+
+impl Lockout {
+    /// Create a `Lockout` that ignores the switch for `delay` after each
+    /// change. Set `delay` to `Duration::ZERO` to disable debouncing.
+    pub fn new(delay: Duration) -> Self {
+        Lockout {
+            delay,
+            state: false,
+            locked_until: None,
+        }
+    }
+}
+
+impl Debouncer for Lockout {
+    fn update(&mut self, now: Instant, raw: bool) -> bool {
+        match self.locked_until {
+            // still settling
+            Some(until) if now < until => return self.state,
+            _ => self.locked_until = None,
+        }
+        if raw != self.state {
+            self.state = raw;
+            if !self.delay.is_zero() {
+                self.locked_until = Some(now + self.delay);
+            }
+        }
+        self.state
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// Counts up for each pressed reading and down for each released one,
+/// changing state only when the count hits either end.
+///
+/// Rides out bounces and noise well. Reacts `samples` updates after the
+/// switch settles, so the delay depends on how often it's updated.
+#[derive(Clone, Copy, Debug)]
+pub struct Integrator {
+    /// The count at which the button is pressed
+    samples: u8,
+    count: u8,
+    state: bool,
+}
+
+impl Integrator {
+    /// Create an `Integrator` that needs `samples` more pressed than
+    /// released readings to change to pressed (and vice versa).
+    ///
+    /// # Panics
+    /// Will panic if `samples` is zero.
+    pub fn new(samples: u8) -> Self {
+        assert!(0 < samples, "an integrator needs at least one sample");
+        Integrator {
+            samples,
+            count: 0,
+            state: false,
+        }
+    }
+}
+
+impl Debouncer for Integrator {
+    fn update(&mut self, _now: Instant, raw: bool) -> bool {
+        if raw {
+            self.count = self.count.saturating_add(1).min(self.samples);
+        } else {
+            self.count = self.count.saturating_sub(1);
+        }
+        if self.samples == self.count {
+            self.state = true;
+        } else if 0 == self.count {
+            self.state = false;
+        }
+        self.state
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// Keeps the last few readings, changing state only once all of them
+/// agree.
+///
+/// The strictest filter here: a single stray reading restarts the wait.
+#[derive(Clone, Copy, Debug)]
+pub struct ShiftRegister {
+    /// The most recent readings, newest in the lowest bit
+    history: u32,
+    /// Selects the readings that count
+    mask: u32,
+    state: bool,
+}
+
+impl ShiftRegister {
+    /// Create a `ShiftRegister` that waits for `samples` matching readings
+    /// in a row.
+    ///
+    /// # Panics
+    /// Will panic if `samples` isn't between 1 and 32.
+    pub fn new(samples: u8) -> Self {
+        ShiftRegister {
+            history: 0,
+            mask: window_mask(samples),
+            state: false,
+        }
+    }
+}
+
+impl Debouncer for ShiftRegister {
+    fn update(&mut self, _now: Instant, raw: bool) -> bool {
+        self.history = (self.history << 1) | u32::from(raw);
+        match self.history & self.mask {
+            0 => self.state = false,
+            all if all == self.mask => self.state = true,
+            _ => (),
+        }
+        self.state
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// Takes a vote over the last few readings: the button is pressed while
+/// more than half of them are.
+///
+/// Tolerates occasional noise without restarting, unlike `ShiftRegister`.
+#[derive(Clone, Copy, Debug)]
+pub struct Majority {
+    /// The most recent readings, newest in the lowest bit
+    history: u32,
+    /// Selects the readings that count
+    mask: u32,
+    state: bool,
+}
+
+impl Majority {
+    /// Create a `Majority` filter voting over the last `samples` readings.
+    /// An odd number avoids ties, which keep the previous state.
+    ///
+    /// # Panics
+    /// Will panic if `samples` isn't between 1 and 32.
+    pub fn new(samples: u8) -> Self {
+        Majority {
+            history: 0,
+            mask: window_mask(samples),
+            state: false,
+        }
+    }
+}
+
+impl Debouncer for Majority {
+    fn update(&mut self, _now: Instant, raw: bool) -> bool {
+        self.history = (self.history << 1) | u32::from(raw);
+        let window = self.mask.count_ones();
+        let pressed = (self.history & self.mask).count_ones();
+        match (2 * pressed).cmp(&window) {
+            core::cmp::Ordering::Greater => self.state = true,
+            core::cmp::Ordering::Less => self.state = false,
+            core::cmp::Ordering::Equal => (),
+        }
+        self.state
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// Requires the switch to hold steady in its new state for a while before
+/// accepting it, with separate delays for pressing and releasing.
+///
+/// Useful for switches that bounce much more one way than the other, or
+/// to make releases forgiving of a wavering finger.
+#[derive(Clone, Copy, Debug)]
+pub struct Asymmetric {
+    /// How long the switch must stay pressed to count as a press
+    press: Duration,
+    /// How long the switch must stay released to count as a release
+    release: Duration,
+    state: bool,
+    /// When the switch started disagreeing with `state`, if it is
+    changed_at: Option<Instant>,
+}
+
+impl Asymmetric {
+    /// Create an `Asymmetric` debouncer.
+    ///
+    /// # Params
+    /// * `press` - How long the switch must stay pressed to count as a
+    ///   press.
+    /// * `release` - How long the switch must stay released to count as a
+    ///   release.
+    pub fn new(press: Duration, release: Duration) -> Self {
+        Asymmetric {
+            press,
+            release,
+            state: false,
+            changed_at: None,
+        }
+    }
+}
+
+impl Debouncer for Asymmetric {
+    fn update(&mut self, now: Instant, raw: bool) -> bool {
+        if raw == self.state {
+            // any change didn't last
+            self.changed_at = None;
+            return self.state;
+        }
+        let since = *self.changed_at.get_or_insert(now);
+        let delay = if raw { self.press } else { self.release };
+        if now - since >= delay {
+            self.state = raw;
+            self.changed_at = None;
+        }
+        self.state
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.state
+    }
+}
+
+/// A mask selecting the lowest `samples` bits.
+///
+/// # Panics
+/// Will panic if `samples` isn't between 1 and 32.
+fn window_mask(samples: u8) -> u32 {
+    assert!(
+        0 < samples && samples <= 32,
+        "the window must be 1 to 32 samples"
+    );
+    u32::MAX >> (32 - u32::from(samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrator_saturates_at_most_samples() {
+        let now = Instant::from_ms(0);
+        let mut integrator = Integrator::new(u8::MAX);
+        for _ in 0..254 {
+            assert!(!integrator.update(now, true));
+        }
+        // stays pressed, rather than overflowing, however long it's held
+        for _ in 0..10 {
+            assert!(integrator.update(now, true));
+        }
+        for _ in 0..254 {
+            assert!(integrator.update(now, false));
+        }
+        assert!(!integrator.update(now, false));
+    }
+
+    /// Feeds raw readings (at ms, pressed) to a debouncer, returning what
+    /// it made of each.
+    fn feed<D: Debouncer>(debouncer: &mut D, readings: &[(u32, bool)]) -> Vec<bool> {
+        readings
+            .iter()
+            .map(|&(ms, raw)| debouncer.update(Instant::from_ms(ms), raw))
+            .collect()
+    }
+
+    /// Feeds raw readings, one per ms from 0, given as 0s and 1s.
+    fn feed_bits<D: Debouncer>(debouncer: &mut D, bits: &str) -> String {
+        let readings: Vec<(u32, bool)> = bits
+            .bytes()
+            .enumerate()
+            .map(|(ms, bit)| (ms as u32, b'1' == bit))
+            .collect();
+        feed(debouncer, &readings)
+            .into_iter()
+            .map(|pressed| if pressed { '1' } else { '0' })
+            .collect()
+    }
+
+    #[test]
+    fn lockout_ignores_bounces_while_settling() {
+        let mut lockout = Lockout::new(Duration::from_ms(10));
+        assert_eq!(
+            vec![true, true, true, true, false],
+            feed(
+                &mut lockout,
+                &[(0, true), (2, false), (5, true), (9, false), (10, false)]
+            )
+        );
+        // a release locks it out too
+        assert_eq!(
+            vec![false, true],
+            feed(&mut lockout, &[(15, true), (20, true)])
+        );
+    }
+
+    #[test]
+    fn asymmetric_settles_each_way_separately() {
+        let mut asymmetric = Asymmetric::new(Duration::from_ms(20), Duration::from_ms(50));
+        assert_eq!(
+            vec![false, false, false, false, true],
+            // the bounce at 12 restarts the wait for a press
+            feed(
+                &mut asymmetric,
+                &[(0, true), (11, true), (12, false), (13, true), (33, true)]
+            )
+        );
+        assert_eq!(
+            vec![true, true, true, false],
+            feed(
+                &mut asymmetric,
+                &[(100, false), (120, false), (149, false), (150, false)]
+            )
+        );
+    }
+
+    #[test]
+    fn shift_register_waits_for_a_clean_run() {
+        let mut shift = ShiftRegister::new(4);
+        // a stray reading restarts the wait, both ways
+        assert_eq!(
+            "000000011111111000",
+            feed_bits(&mut shift, "111011110001000000")
+        );
+    }
+
+    #[test]
+    fn majority_outvotes_the_odd_reading() {
+        let mut majority = Majority::new(5);
+        assert_eq!("00011110", feed_bits(&mut majority, "10110100"));
+    }
+}
@@ -0,0 +1,552 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::button::{InputKind, InputSource};
+use super::time::{Duration, Instant};
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal::digital::v2::InputPin;
+
+/// Quarter steps for each transition, indexed by the previous reading
+/// times four plus the new one (A in bit 1, B in bit 0). Clockwise is
+/// 00 -> 10 -> 11 -> 01 -> 00, with A leading B.
+const QUARTERS: [i8; 16] = [
+    // to: 00  01  10  11      from
+    0, -1, 1, 0, //           00
+    1, 0, 0, -1, //           01
+    -1, 0, 0, 1, //           10
+    0, 1, -1, 0, //           11
+];
+
+/// Turns a sequence of readings of an encoder's A and B pins into detent
+/// steps.
+///
+/// Knows nothing about pins or time, so it can be fed recorded readings.
+///
+/// # Examples
+///
+/// ```
+/// use timer::encoder::Decoder;
+///
+/// let mut decoder = Decoder::new(4);
+/// // one detent clockwise
+/// let steps: i32 = [(true, false), (true, true), (false, true), (false, false)]
+///     .iter()
+///     .map(|&(a, b)| i32::from(decoder.update(a, b)))
+///     .sum();
+/// assert_eq!(1, steps);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Decoder {
+    /// The last reading, A in bit 1 and B in bit 0
+    state: u8,
+    /// Quarter steps since the last detent
+    quarters: i8,
+    /// Quarter steps from one detent to the next
+    quarters_per_detent: i8,
+    /// How many readings skipped a quarter step
+    invalid: u16,
+}
+
+impl Decoder {
+    /// Create a `Decoder` for an encoder with `quarters_per_detent` (1, 2
+    /// or 4; usually 4) quarter steps from one detent to the next, which
+    /// starts out with both pins low.
+    pub const fn new(quarters_per_detent: u8) -> Self {
+        Decoder {
+            state: 0,
+            quarters: 0,
+            quarters_per_detent: quarters_per_detent as i8,
+            invalid: 0,
+        }
+    }
+
+    /// Start over from the reading `a`, `b`, e.g. the pins' levels at
+    /// startup.
+    pub fn reset(&mut self, a: bool, b: bool) {
+        self.state = reading(a, b);
+        self.quarters = 0;
+    }
+
+    /// Take a new reading of the A and B pins.
+    ///
+    /// A reading where both pins changed at once means a quarter step was
+    /// missed, so which way the encoder turned is unknown. It's counted
+    /// (see `invalid_transitions`) and otherwise ignored.
+    ///
+    /// # Return
+    /// 1 if the encoder reached the next detent clockwise, -1 if counter-
+    /// clockwise, or 0
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let new = reading(a, b);
+        if new == self.state {
+            return 0;
+        }
+        let old = self.state;
+        self.state = new;
+        if 0b11 == old ^ new {
+            self.invalid = self.invalid.saturating_add(1);
+            // the partial detent can't be trusted any more
+            self.quarters = 0;
+            return 0;
+        }
+
+        self.quarters += QUARTERS[usize::from(old << 2 | new)];
+        let per_detent = self.quarters_per_detent.max(1);
+        if self.quarters >= per_detent {
+            self.quarters -= per_detent;
+            1
+        } else if self.quarters <= -per_detent {
+            self.quarters += per_detent;
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// How many readings have skipped a quarter step, which usually means
+    /// the encoder isn't being read often enough.
+    pub fn invalid_transitions(&self) -> u16 {
+        self.invalid
+    }
+}
+
+/// Packs a reading of the A and B pins into two bits.
+fn reading(a: bool, b: bool) -> u8 {
+    u8::from(a) << 1 | u8::from(b)
+}
+
+/// How turning the encoder faster moves further per detent.
+///
+/// Turning with `slow` or more between detents moves one step per detent;
+/// `fast` or less moves `max_multiplier` steps; in between, it scales
+/// linearly.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccelCurve {
+    /// The gap between detents at or above which there's no acceleration
+    pub slow: Duration,
+    /// The gap between detents at or below which acceleration is greatest
+    pub fast: Duration,
+    /// The most steps a single detent can move
+    pub max_multiplier: u8,
+}
+
+impl AccelCurve {
+    /// One step per detent, however fast the encoder turns
+    pub const NONE: AccelCurve = AccelCurve {
+        slow: Duration::ZERO,
+        fast: Duration::ZERO,
+        max_multiplier: 1,
+    };
+
+    /// Precise when turned slowly, up to ten steps per detent when spun
+    pub const DEFAULT: AccelCurve = AccelCurve {
+        slow: Duration::from_ms(100),
+        fast: Duration::from_ms(10),
+        max_multiplier: 10,
+    };
+
+    /// How many steps one detent moves, `gap` after the last.
+    pub fn multiplier(&self, gap: Duration) -> u32 {
+        let max = u32::from(self.max_multiplier.max(1));
+        if gap >= self.slow {
+            1
+        } else if gap <= self.fast {
+            max
+        } else {
+            // gap is strictly between fast and slow here
+            let range = (self.slow - self.fast).as_ms();
+            let speed = (self.slow - gap).as_ms();
+            1 + (max - 1) * speed / range
+        }
+    }
+}
+
+impl Default for AccelCurve {
+    fn default() -> AccelCurve {
+        AccelCurve::DEFAULT
+    }
+}
+
+/// Scales detent steps by how fast the encoder is turning.
+///
+/// # Examples
+///
+/// ```
+/// use timer::encoder::{AccelCurve, Accelerator};
+/// use timer::time::{Duration, Instant};
+///
+/// let mut accel = Accelerator::new(AccelCurve::DEFAULT);
+/// let start = Instant::from_ms(1000);
+/// // the first detent after a rest isn't accelerated
+/// assert_eq!(1, accel.apply(start, 1));
+/// // but one quickly after it is
+/// assert_eq!(10, accel.apply(start + Duration::from_ms(5), 1));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Accelerator {
+    curve: AccelCurve,
+    /// When the last detent step came, and which way it went
+    last: Option<(Instant, i32)>,
+}
+
+impl Accelerator {
+    /// Create an `Accelerator` following `curve`.
+    pub fn new(curve: AccelCurve) -> Self {
+        Accelerator { curve, last: None }
+    }
+
+    /// The curve currently in use.
+    pub fn curve(&self) -> AccelCurve {
+        self.curve
+    }
+
+    /// Change the curve.
+    pub fn set_curve(&mut self, curve: AccelCurve) {
+        self.curve = curve;
+    }
+
+    /// Scale `steps` detent steps (negative for counter-clockwise) that
+    /// came since the last call, at `now`. Several steps at once are taken
+    /// as evenly spread since the previous step. Changing direction starts
+    /// again without acceleration.
+    pub fn apply(&mut self, now: Instant, steps: i32) -> i32 {
+        if 0 == steps {
+            return 0;
+        }
+        let multiplier = match self.last {
+            Some((then, direction)) if direction == steps.signum() => {
+                let gap = (now - then) / steps.unsigned_abs();
+                self.curve.multiplier(gap)
+            }
+            _ => 1,
+        };
+        self.last = Some((now, steps.signum()));
+        steps * multiplier as i32
+    }
+}
+
+/// A rotary encoder on two pins, read by polling.
+///
+/// `update` must be called often enough to see every quarter step, which
+/// for a quickly spun knob may mean every millisecond or so; otherwise
+/// steps are lost (see `invalid_transitions`). `ExtiEncoder` doesn't have
+/// that problem.
+pub struct Encoder<A, B> {
+    a: A,
+    b: B,
+    decoder: Decoder,
+    accel: Accelerator,
+}
+
+impl<A: InputPin, B: InputPin> Encoder<A, B> {
+    /// Create a new Encoder.
+    ///
+    /// # Params
+    /// * `a` - The pin the encoder's A output is on.
+    /// * `b` - The pin the encoder's B output is on.
+    /// * `quarters_per_detent` - Quarter steps from one detent to the next
+    ///   (1, 2 or 4; usually 4).
+    /// * `curve` - How turning faster moves further per detent.
+    pub fn new(a: A, b: B, quarters_per_detent: u8, curve: AccelCurve) -> Self {
+        let mut decoder = Decoder::new(quarters_per_detent);
+        decoder.reset(a.is_high().unwrap_or(false), b.is_high().unwrap_or(false));
+        Encoder {
+            a,
+            b,
+            decoder,
+            accel: Accelerator::new(curve),
+        }
+    }
+
+    /// Read the pins and return how many (accelerated) steps the encoder
+    /// has moved, negative for counter-clockwise. If either pin can't be
+    /// read, the encoder is taken not to have moved.
+    pub fn update(&mut self, now: Instant) -> i32 {
+        match (self.a.is_high(), self.b.is_high()) {
+            (Ok(a), Ok(b)) => {
+                let steps = self.decoder.update(a, b);
+                self.accel.apply(now, i32::from(steps))
+            }
+            _ => 0,
+        }
+    }
+
+    /// Change how turning faster moves further per detent.
+    pub fn set_curve(&mut self, curve: AccelCurve) {
+        self.accel.set_curve(curve);
+    }
+
+    /// How many readings have skipped a quarter step.
+    pub fn invalid_transitions(&self) -> u16 {
+        self.decoder.invalid_transitions()
+    }
+
+    /// Release the pins.
+    pub fn free(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: InputPin, B: InputPin> InputSource for Encoder<A, B> {
+    fn poll_input(&mut self, now: Instant) -> Option<InputKind> {
+        match self.update(now) {
+            0 => None,
+            steps => Some(InputKind::Encoder(steps)),
+        }
+    }
+}
+
+/// What the interrupt handler has decoded so far.
+#[derive(Clone, Copy)]
+struct Pending {
+    decoder: Decoder,
+    /// Detent steps not yet collected
+    steps: i32,
+}
+
+/// The interrupt side of an `ExtiEncoder`: decodes edges as they happen,
+/// so no quarter steps are missed however slowly the main loop runs.
+///
+/// Meant to live in a `static`, fed from the EXTI handlers for both pins.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::encoder::SharedDecoder;
+///
+/// static KNOB: SharedDecoder = SharedDecoder::new(4);
+///
+/// # fn read_a() -> bool { false }
+/// # fn read_b() -> bool { false }
+/// // in the EXTI handler for both pins, after clearing the pending bits
+/// KNOB.edge(read_a(), read_b());
+/// ```
+pub struct SharedDecoder {
+    pending: Mutex<Cell<Pending>>,
+}
+
+impl SharedDecoder {
+    /// Create a `SharedDecoder` for an encoder with `quarters_per_detent`
+    /// quarter steps from one detent to the next.
+    pub const fn new(quarters_per_detent: u8) -> Self {
+        SharedDecoder {
+            pending: Mutex::new(Cell::new(Pending {
+                decoder: Decoder::new(quarters_per_detent),
+                steps: 0,
+            })),
+        }
+    }
+
+    /// Start over from the reading `a`, `b`, e.g. the pins' levels at
+    /// startup.
+    pub fn reset(&self, a: bool, b: bool) {
+        interrupt::free(|cs| {
+            let cell = self.pending.borrow(cs);
+            let mut pending = cell.get();
+            pending.decoder.reset(a, b);
+            pending.steps = 0;
+            cell.set(pending);
+        })
+    }
+
+    /// Take a reading of the A and B pins. Call from the EXTI handler
+    /// whenever either pin changes.
+    pub fn edge(&self, a: bool, b: bool) {
+        interrupt::free(|cs| {
+            let cell = self.pending.borrow(cs);
+            let mut pending = cell.get();
+            let step = pending.decoder.update(a, b);
+            pending.steps = pending.steps.saturating_add(i32::from(step));
+            cell.set(pending);
+        })
+    }
+
+    /// Collect the detent steps since the last call.
+    pub fn take(&self) -> i32 {
+        interrupt::free(|cs| {
+            let cell = self.pending.borrow(cs);
+            let mut pending = cell.get();
+            let steps = pending.steps;
+            pending.steps = 0;
+            cell.set(pending);
+            steps
+        })
+    }
+
+    /// How many readings have skipped a quarter step.
+    pub fn invalid_transitions(&self) -> u16 {
+        interrupt::free(|cs| self.pending.borrow(cs).get().decoder.invalid_transitions())
+    }
+}
+
+/// A rotary encoder decoded in EXTI interrupt handlers by a
+/// `SharedDecoder`, with acceleration applied in the main loop.
+pub struct ExtiEncoder {
+    shared: &'static SharedDecoder,
+    accel: Accelerator,
+}
+
+impl ExtiEncoder {
+    /// Create an `ExtiEncoder` collecting steps from `shared`, accelerated
+    /// according to `curve`.
+    pub fn new(shared: &'static SharedDecoder, curve: AccelCurve) -> Self {
+        ExtiEncoder {
+            shared,
+            accel: Accelerator::new(curve),
+        }
+    }
+
+    /// Return how many (accelerated) steps the encoder has moved since the
+    /// last call, negative for counter-clockwise.
+    pub fn update(&mut self, now: Instant) -> i32 {
+        self.accel.apply(now, self.shared.take())
+    }
+
+    /// Change how turning faster moves further per detent.
+    pub fn set_curve(&mut self, curve: AccelCurve) {
+        self.accel.set_curve(curve);
+    }
+
+    /// How many readings have skipped a quarter step.
+    pub fn invalid_transitions(&self) -> u16 {
+        self.shared.invalid_transitions()
+    }
+}
+
+impl InputSource for ExtiEncoder {
+    fn poll_input(&mut self, now: Instant) -> Option<InputKind> {
+        match self.update(now) {
+            0 => None,
+            steps => Some(InputKind::Encoder(steps)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    const CW: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+    const CCW: [(bool, bool); 4] = [(false, true), (true, true), (true, false), (false, false)];
+
+    /// Feeds `readings` to `decoder`, returning the detent steps.
+    fn feed(decoder: &mut Decoder, readings: &[(bool, bool)]) -> i32 {
+        readings
+            .iter()
+            .map(|&(a, b)| i32::from(decoder.update(a, b)))
+            .sum()
+    }
+
+    #[test]
+    fn decodes_detents_both_ways() {
+        let mut decoder = Decoder::new(4);
+        assert_eq!(1, feed(&mut decoder, &CW));
+        assert_eq!(1, feed(&mut decoder, &CW));
+        assert_eq!(-1, feed(&mut decoder, &CCW));
+        // half way round and back again is no step at all
+        assert_eq!(0, feed(&mut decoder, &CW[..2]));
+        assert_eq!(0, feed(&mut decoder, &[(true, false), (false, false)]));
+        assert_eq!(0, decoder.invalid_transitions());
+
+        let mut decoder = Decoder::new(2);
+        assert_eq!(2, feed(&mut decoder, &CW));
+        assert_eq!(-2, feed(&mut decoder, &CCW));
+    }
+
+    #[test]
+    fn rides_out_contact_bounce() {
+        let mut decoder = Decoder::new(4);
+        // A bounces on its way up, and B on its way down
+        let bouncy = [
+            (true, false),
+            (false, false),
+            (true, false),
+            (true, true),
+            (false, true),
+            (false, false),
+            (false, true),
+            (false, false),
+        ];
+        assert_eq!(1, feed(&mut decoder, &bouncy));
+        assert_eq!(0, decoder.invalid_transitions());
+    }
+
+    #[test]
+    fn counts_and_ignores_invalid_transitions() {
+        let mut decoder = Decoder::new(4);
+        // three quarters of a detent, then a skipped quarter step
+        assert_eq!(0, feed(&mut decoder, &CW[..3]));
+        assert_eq!(0, feed(&mut decoder, &[(true, false)]));
+        assert_eq!(1, decoder.invalid_transitions());
+        // the partial detent was thrown away, so finishing it does nothing
+        assert_eq!(
+            0,
+            feed(&mut decoder, &[(true, true), (false, true), (false, false)])
+        );
+        assert_eq!(1, feed(&mut decoder, &CW));
+    }
+
+    #[test]
+    fn multiplier_follows_the_curve() {
+        let curve = AccelCurve::DEFAULT;
+        assert_eq!(1, curve.multiplier(Duration::from_ms(500)));
+        assert_eq!(1, curve.multiplier(Duration::from_ms(100)));
+        assert_eq!(5, curve.multiplier(Duration::from_ms(55)));
+        assert_eq!(10, curve.multiplier(Duration::from_ms(10)));
+        assert_eq!(10, curve.multiplier(Duration::ZERO));
+        assert_eq!(1, AccelCurve::NONE.multiplier(Duration::ZERO));
+    }
+
+    #[test]
+    fn accelerates_steady_turning() {
+        let mut accel = Accelerator::new(AccelCurve::DEFAULT);
+        assert_eq!(1, accel.apply(Instant::from_ms(1000), 1));
+        assert_eq!(10, accel.apply(Instant::from_ms(1005), 1));
+        // changing direction starts again without acceleration
+        assert_eq!(-1, accel.apply(Instant::from_ms(1010), -1));
+        // two steps in 100 ms are 50 ms apart
+        assert_eq!(-12, accel.apply(Instant::from_ms(1110), -2));
+        assert_eq!(0, accel.apply(Instant::from_ms(1111), 0));
+    }
+
+    /// A pin that reads whatever it's set to.
+    struct Pin<'a>(&'a Cell<bool>);
+
+    impl InputPin for Pin<'_> {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.get())
+        }
+    }
+
+    #[test]
+    fn encoder_reads_its_pins() {
+        let (a, b) = (Cell::new(false), Cell::new(false));
+        let mut encoder = Encoder::new(Pin(&a), Pin(&b), 4, AccelCurve::DEFAULT);
+        let mut turn = |start: u32, readings: &[(bool, bool)]| -> i32 {
+            let mut steps = 0;
+            for (ms, &(high_a, high_b)) in (start..).zip(readings) {
+                a.set(high_a);
+                b.set(high_b);
+                steps += encoder.update(Instant::from_ms(ms));
+            }
+            steps
+        };
+        assert_eq!(1, turn(0, &CW));
+        // the next detent comes 1 ms later, as fast as it gets
+        assert_eq!(10, turn(1, &CW));
+        // turning back starts again without acceleration
+        assert_eq!(-1, turn(5, &CCW));
+    }
+}
@@ -0,0 +1,9 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+/// Returned when a fixed-capacity container (a `Scheduler`, `EdgeQueue`,
+/// `Supervisor`, etc.) has no free slots left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Full;
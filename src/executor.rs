@@ -0,0 +1,192 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::error::Full;
+#[cfg(feature = "board")]
+use super::systick::Systick;
+use super::time::{Duration, Instant};
+
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The time as of the executor's latest tick, in ms
+static NOW: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the time as of the executor's latest tick. This is what `Timer`
+/// measures against; it doesn't change while tasks are being polled.
+pub fn now() -> Instant {
+    Instant::from_ms(NOW.load(Ordering::Relaxed))
+}
+
+/// A future that completes once a deadline has passed.
+pub struct Timer {
+    deadline: Instant,
+}
+
+impl Timer {
+    /// Completes `delay` after the current tick.
+    pub fn after(delay: Duration) -> Timer {
+        Timer {
+            deadline: now() + delay,
+        }
+    }
+
+    /// Completes on the first tick at or after `deadline`.
+    pub fn at(deadline: Instant) -> Timer {
+        Timer { deadline }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        if now() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A future that completes on the next tick.
+pub struct NextTick {
+    started: Instant,
+}
+
+/// Waits until the next tick, letting other tasks run in the meantime.
+pub fn next_tick() -> NextTick {
+    NextTick { started: now() }
+}
+
+impl Future for NextTick {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        if now() != self.started {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs up to `N` tasks cooperatively, polling each of them once per
+/// `Systick` tick. Needs no allocator: tasks are pinned wherever the
+/// caller likes (usually the stack of `main`, which never returns).
+///
+/// Every unfinished task is polled on every tick, so wakers aren't needed
+/// and the ones handed to tasks do nothing.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::pin::pin;
+/// use timer::executor::{self, Executor, Timer};
+/// use timer::time::Duration;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn sleep_til_wrapped(&mut self) {}
+/// # }
+/// # fn run(mut systick: Systick) -> ! {
+///
+/// async fn blink() {
+///     loop {
+///         // turn an LED on
+///         Timer::after(Duration::from_ms(500)).await;
+///         // turn it off
+///         Timer::after(Duration::from_ms(500)).await;
+///     }
+/// }
+///
+/// let blinker = pin!(blink());
+/// let mut executor: Executor<1> = Executor::new();
+/// executor.spawn(blinker).unwrap();
+/// // the same as `executor.run(&mut systick)`
+/// loop {
+///     executor.poll(systick.now());
+///     systick.sleep_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Executor<'a, const N: usize> {
+    /// Tasks that haven't finished yet
+    tasks: [Option<Pin<&'a mut dyn Future<Output = ()>>>; N],
+}
+
+impl<'a, const N: usize> Executor<'a, N> {
+    /// Create an `Executor` with no tasks.
+    pub fn new() -> Self {
+        Executor {
+            tasks: core::array::from_fn(|_| None),
+        }
+    }
+
+    /// Add a task to be run.
+    pub fn spawn(&mut self, task: Pin<&'a mut dyn Future<Output = ()>>) -> Result<(), Full> {
+        let slot = self.tasks.iter_mut().find(|t| t.is_none()).ok_or(Full)?;
+        *slot = Some(task);
+        Ok(())
+    }
+
+    /// Polls every unfinished task once, as of `now`.
+    ///
+    /// # Return
+    /// `true` if any tasks are still unfinished
+    pub fn poll(&mut self, now: Instant) -> bool {
+        NOW.store(now.as_ms(), Ordering::Relaxed);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut unfinished = false;
+        for slot in self.tasks.iter_mut() {
+            if let Some(task) = slot {
+                if task.as_mut().poll(&mut cx).is_ready() {
+                    *slot = None;
+                } else {
+                    unfinished = true;
+                }
+            }
+        }
+        unfinished
+    }
+
+    /// Runs the tasks forever, polling them after each tick.
+    #[cfg(feature = "board")]
+    pub fn run(&mut self, systick: &mut Systick) -> ! {
+        loop {
+            self.poll(systick.now());
+            systick.sleep_til_wrapped();
+        }
+    }
+}
+
+impl<'a, const N: usize> Default for Executor<'a, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A waker that does nothing, since every task is polled every tick.
+fn noop_waker() -> Waker {
+    // This is safe because none of the vtable functions touch the data
+    // pointer.
+    unsafe { Waker::from_raw(noop_raw_waker()) }
+}
+
+fn noop_raw_waker() -> RawWaker {
+    RawWaker::new(ptr::null(), &NOOP_VTABLE)
+}
+
+fn noop_clone(_: *const ()) -> RawWaker {
+    noop_raw_waker()
+}
+
+fn noop(_: *const ()) {}
+
+static NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(noop_clone, noop, noop, noop);
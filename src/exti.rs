@@ -0,0 +1,235 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::error::Full;
+#[cfg(feature = "board")]
+use super::systick;
+use super::time::Instant;
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "board")]
+use f3::hal::stm32f30x::{EXTI, RCC, SYSCFG};
+
+/// A change in a pin's level, timestamped when it happened.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Edge {
+    /// When the pin changed
+    pub at: Instant,
+    /// `true` if the pin went high
+    pub high: bool,
+}
+
+/// A fixed-capacity queue of `Edge`s, for passing them from an EXTI
+/// interrupt handler to the main loop without disabling interrupts.
+///
+/// `split` it into one `EdgeProducer`, for the interrupt handler, and one
+/// `EdgeConsumer`, for the main loop.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::exti::{Edge, EdgeQueue};
+/// # use timer::time::Instant;
+///
+/// static mut QUEUE: EdgeQueue<16> = EdgeQueue::new();
+///
+/// // This is safe because the queue is only ever split here.
+/// let (mut producer, mut consumer) = unsafe { QUEUE.split() };
+///
+/// // in the EXTI handler, after clearing the pending bit (on the board,
+/// // `producer.record(pin_is_high)` does the timestamping)
+/// # let pin_is_high = true;
+/// # let now = Instant::from_ms(0);
+/// producer.push(Edge { at: now, high: pin_is_high }).ok();
+///
+/// // in the main loop
+/// while let Some(edge) = consumer.pop() {
+///     // button.feed(edge);
+/// }
+/// ```
+pub struct EdgeQueue<const N: usize> {
+    buffer: UnsafeCell<[Edge; N]>,
+    /// How many edges have been taken out, wrapping
+    head: AtomicUsize,
+    /// How many edges have been put in, wrapping
+    tail: AtomicUsize,
+}
+
+// This is safe because the buffer is only reached through the one
+// producer and one consumer `split` hands out, which never touch the same
+// slot at once.
+unsafe impl<const N: usize> Sync for EdgeQueue<N> {}
+
+impl<const N: usize> EdgeQueue<N> {
+    /// Create an empty `EdgeQueue`. `N` must be a power of two.
+    pub const fn new() -> Self {
+        EdgeQueue {
+            buffer: UnsafeCell::new(
+                [Edge {
+                    at: Instant::from_ms(0),
+                    high: false,
+                }; N],
+            ),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Split the queue into the end that puts edges in and the end that
+    /// takes them out.
+    ///
+    /// # Panics
+    /// Will panic if `N` isn't a power of two.
+    pub fn split(&mut self) -> (EdgeProducer<'_, N>, EdgeConsumer<'_, N>) {
+        assert!(
+            N.is_power_of_two(),
+            "the queue length must be a power of two"
+        );
+        (
+            EdgeProducer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+            EdgeConsumer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    /// A pointer to the slot `count` refers to.
+    fn slot(&self, count: usize) -> *mut Edge {
+        // This is safe because `count % N` is always in bounds.
+        unsafe { (self.buffer.get() as *mut Edge).add(count % N) }
+    }
+}
+
+impl<const N: usize> Default for EdgeQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The end of an `EdgeQueue` that puts edges in. Belongs in the EXTI
+/// interrupt handler.
+pub struct EdgeProducer<'a, const N: usize> {
+    queue: &'a EdgeQueue<N>,
+    /// Can be sent to the interrupt handler, but not shared with it
+    _not_sync: PhantomData<core::cell::Cell<()>>,
+}
+
+impl<'a, const N: usize> EdgeProducer<'a, N> {
+    /// Add an edge, unless the queue is full.
+    pub fn push(&mut self, edge: Edge) -> Result<(), Full> {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let head = self.queue.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= N {
+            return Err(Full);
+        }
+        // This is safe because the consumer doesn't read this slot until
+        // `tail` is moved past it.
+        unsafe { self.queue.slot(tail).write(edge) };
+        self.queue
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Add an edge to `high` (or low), timestamped now.
+    ///
+    /// The timestamp includes how far the SysTick counter is through the
+    /// current tick, so it's accurate to the ms rather than to the tick.
+    /// Needs `Systick::enable_interrupt` to have been called.
+    #[cfg(feature = "board")]
+    pub fn record(&mut self, high: bool) -> Result<(), Full> {
+        let at = Instant::from_ms((systick::now_us() / 1000) as u32);
+        self.push(Edge { at, high })
+    }
+}
+
+/// The end of an `EdgeQueue` that takes edges out. Belongs in the main
+/// loop.
+pub struct EdgeConsumer<'a, const N: usize> {
+    queue: &'a EdgeQueue<N>,
+    /// Can be sent elsewhere, but not shared
+    _not_sync: PhantomData<core::cell::Cell<()>>,
+}
+
+impl<'a, const N: usize> EdgeConsumer<'a, N> {
+    /// Take out the oldest edge, if there are any.
+    pub fn pop(&mut self) -> Option<Edge> {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // This is safe because the producer doesn't write this slot again
+        // until `head` is moved past it.
+        let edge = unsafe { self.queue.slot(head).read() };
+        self.queue
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(edge)
+    }
+
+    /// `true` if there are no edges waiting.
+    pub fn is_empty(&self) -> bool {
+        self.queue.head.load(Ordering::Relaxed) == self.queue.tail.load(Ordering::Acquire)
+    }
+}
+
+/// A GPIO port, for routing its pins to EXTI lines.
+#[cfg(feature = "board")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+/// Routes pin `line` of `port` to EXTI line `line`, and has the line
+/// interrupt on both rising and falling edges. Unmask the line's interrupt
+/// in the NVIC afterwards (e.g. `EXTI0` for line 0, `EXTI1` for line 1).
+///
+/// # Panics
+/// Will panic if `line` is more than 15.
+#[cfg(feature = "board")]
+pub fn listen(exti: &EXTI, syscfg: &SYSCFG, port: Port, line: u8) {
+    assert!(line < 16, "GPIO pins only go up to 15");
+    // These are safe because every value written is one the registers
+    // accept, and nothing else is configuring them at the same time.
+    unsafe {
+        // SYSCFGEN: clock the SYSCFG so its registers can be written
+        (*RCC::ptr()).apb2enr.modify(|r, w| w.bits(r.bits() | 1));
+
+        let shift = 4 * u32::from(line % 4);
+        let select = |r: u32| (r & !(0xF << shift)) | ((port as u32) << shift);
+        match line / 4 {
+            0 => syscfg.exticr1.modify(|r, w| w.bits(select(r.bits()))),
+            1 => syscfg.exticr2.modify(|r, w| w.bits(select(r.bits()))),
+            2 => syscfg.exticr3.modify(|r, w| w.bits(select(r.bits()))),
+            _ => syscfg.exticr4.modify(|r, w| w.bits(select(r.bits()))),
+        }
+
+        let mask = 1 << line;
+        exti.rtsr1.modify(|r, w| w.bits(r.bits() | mask));
+        exti.ftsr1.modify(|r, w| w.bits(r.bits() | mask));
+        exti.imr1.modify(|r, w| w.bits(r.bits() | mask));
+    }
+}
+
+/// Clears EXTI line `line`'s pending bit. Call this in the line's
+/// interrupt handler, or it will fire again straight away.
+#[cfg(feature = "board")]
+pub fn clear(exti: &EXTI, line: u8) {
+    // This is safe because writing 1 only clears that line's bit.
+    exti.pr1.write(|w| unsafe { w.bits(1 << line) });
+}
@@ -0,0 +1,336 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::button::MultiButtonEvent;
+use super::time::{Duration, Instant};
+
+/// One part of a gesture, as reported by a `FancyButton`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Step {
+    /// `n` presses in quick succession (`MultiButtonEvent::Press(n)`)
+    Presses(u8),
+    /// A hold after `n` quick presses (`MultiButtonEvent::HoldStart(n)`)
+    Hold(u8),
+    /// The hold reached `long_hold` (`MultiButtonEvent::LongHold`)
+    LongHold,
+    /// The hold reached `very_long_hold` (`MultiButtonEvent::VeryLongHold`)
+    VeryLongHold,
+}
+
+/// A sequence of steps, and the command it stands for.
+#[derive(Clone, Copy, Debug)]
+pub struct Gesture<'a, C> {
+    /// What the user does
+    pub steps: &'a [Step],
+    /// What it means
+    pub command: C,
+}
+
+/// How the steps so far compare with the gestures.
+enum Outcome<C> {
+    /// They make up exactly one gesture, and begin no others
+    Done(C),
+    /// They begin at least one gesture
+    Wait,
+    /// They don't make up or begin any gesture
+    NoMatch,
+}
+
+/// Recognizes gestures, such as "double-click then hold", in the events
+/// from a `FancyButton`, from a table of up to `M`-step gestures.
+///
+/// When the steps so far make up one gesture but also begin a longer one
+/// (a double-click when there's also a double-click then hold), the
+/// recognizer waits `timeout` after the last event for the longer one
+/// before settling for the shorter. Holding the button keeps it waiting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::gesture::{Gesture, Recognizer, Step};
+/// use timer::time::Duration;
+/// # use timer::button::FancyButton;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run<P>(mut systick: Systick, mut button: FancyButton<P>) -> !
+/// # where
+/// #     P: embedded_hal::digital::v2::InputPin,
+/// # {
+///
+/// #[derive(Clone, Copy)]
+/// enum Command {
+///     Progress,
+///     Invert,
+///     Reset,
+/// }
+///
+/// const GESTURES: &[Gesture<Command>] = &[
+///     Gesture { steps: &[Step::Presses(2)], command: Command::Progress },
+///     Gesture { steps: &[Step::Presses(3)], command: Command::Invert },
+///     Gesture { steps: &[Step::Presses(1), Step::Hold(0)], command: Command::Reset },
+/// ];
+///
+/// let mut gestures: Recognizer<Command, 2> = Recognizer::new(GESTURES, Duration::from_ms(600));
+///
+/// loop {
+///     let now = systick.now();
+///     match gestures.update(now, button.update(now)) {
+///         Some(Command::Progress) => { /* move to the next field */ }
+///         Some(Command::Invert) => { /* invert the selection */ }
+///         Some(Command::Reset) => { /* reset the time */ }
+///         None => (),
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Recognizer<'a, C, const M: usize> {
+    gestures: &'a [Gesture<'a, C>],
+    /// How long to wait for the rest of a longer gesture
+    timeout: Duration,
+    /// The steps so far
+    steps: [Step; M],
+    /// How many steps there are so far
+    len: usize,
+    /// When the last event came
+    last_at: Instant,
+    /// A command recognized while returning another
+    queued: Option<C>,
+}
+
+impl<'a, C: Copy, const M: usize> Recognizer<'a, C, M> {
+    /// Create a `Recognizer` for `gestures`, which waits `timeout` for a
+    /// longer gesture when the steps so far could be either.
+    ///
+    /// # Panics
+    /// Will panic if `M` is zero, or a gesture has no steps or more than
+    /// `M` steps.
+    pub fn new(gestures: &'a [Gesture<'a, C>], timeout: Duration) -> Self {
+        assert!(0 < M, "a recognizer needs room for at least one step");
+        assert!(
+            gestures
+                .iter()
+                .all(|g| !g.steps.is_empty() && g.steps.len() <= M),
+            "every gesture needs between 1 and M steps"
+        );
+        Recognizer {
+            gestures,
+            timeout,
+            steps: [Step::Presses(0); M],
+            len: 0,
+            last_at: Instant::default(),
+            queued: None,
+        }
+    }
+
+    /// Take the latest event from the button (or `None`) at `now`.
+    ///
+    /// # Return
+    /// The command for a gesture that has just been completed, if any. At
+    /// most one command is returned per call; if two are recognized at
+    /// once, the second follows on the next call.
+    pub fn update(&mut self, now: Instant, event: Option<MultiButtonEvent>) -> Option<C> {
+        let mut command = self.queued.take();
+        if 0 < self.len && now - self.last_at >= self.timeout {
+            // no longer gesture came, so settle for what there is
+            let timed_out = self.exact();
+            self.len = 0;
+            command = self.either(command, timed_out);
+        }
+        if let Some(event) = event {
+            let matched = self.event(now, event);
+            command = self.either(command, matched);
+        }
+        command
+    }
+
+    /// Forget the steps so far.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.queued = None;
+    }
+
+    /// Take an event from the button.
+    fn event(&mut self, now: Instant, event: MultiButtonEvent) -> Option<C> {
+        use MultiButtonEvent::*;
+        let step = match event {
+            Press(n) => Step::Presses(n),
+            HoldStart(n) => Step::Hold(n),
+            LongHold => Step::LongHold,
+            VeryLongHold => Step::VeryLongHold,
+            // still holding, or just let go: not a step, but the user
+            // isn't done yet
+            Holding(_) | Release(_) => {
+                self.last_at = now;
+                return None;
+            }
+        };
+        self.push(now, step)
+    }
+
+    /// Add a step and see what it makes.
+    fn push(&mut self, now: Instant, step: Step) -> Option<C> {
+        if M == self.len {
+            self.len = 0;
+        }
+        self.steps[self.len] = step;
+        self.len += 1;
+        self.last_at = now;
+
+        match self.evaluate() {
+            Outcome::Done(command) => {
+                self.len = 0;
+                Some(command)
+            }
+            Outcome::Wait => None,
+            Outcome::NoMatch if 1 < self.len => {
+                // the steps before this one may have made a gesture that
+                // was waiting on a longer one; this step may begin another
+                self.len -= 1;
+                let earlier = self.exact();
+                self.len = 0;
+                let later = self.push(now, step);
+                self.either(earlier, later)
+            }
+            Outcome::NoMatch => {
+                self.len = 0;
+                None
+            }
+        }
+    }
+
+    /// Compare the steps so far with the gestures.
+    fn evaluate(&self) -> Outcome<C> {
+        let seen = &self.steps[..self.len];
+        let mut exact = None;
+        let mut longer = false;
+        for gesture in self.gestures {
+            if gesture.steps.starts_with(seen) {
+                if gesture.steps.len() == seen.len() {
+                    // the first of any duplicates wins
+                    exact = exact.or(Some(gesture.command));
+                } else {
+                    longer = true;
+                }
+            }
+        }
+        match (exact, longer) {
+            (_, true) => Outcome::Wait,
+            (Some(command), false) => Outcome::Done(command),
+            (None, false) => Outcome::NoMatch,
+        }
+    }
+
+    /// The gesture the steps so far make up exactly, if any.
+    fn exact(&self) -> Option<C> {
+        let seen = &self.steps[..self.len];
+        self.gestures
+            .iter()
+            .find(|gesture| gesture.steps == seen)
+            .map(|gesture| gesture.command)
+    }
+
+    /// Return `first` if there is one, queueing `second` for the next call;
+    /// otherwise return `second`.
+    fn either(&mut self, first: Option<C>, second: Option<C>) -> Option<C> {
+        match first {
+            Some(command) => {
+                if second.is_some() {
+                    self.queued = second;
+                }
+                Some(command)
+            }
+            None => second,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MultiButtonEvent::*;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    enum Command {
+        Select,
+        Invert,
+        Reset,
+    }
+
+    const GESTURES: &[Gesture<Command>] = &[
+        Gesture {
+            steps: &[Step::Presses(1)],
+            command: Command::Select,
+        },
+        Gesture {
+            steps: &[Step::Presses(3)],
+            command: Command::Invert,
+        },
+        Gesture {
+            steps: &[Step::Presses(1), Step::Hold(0)],
+            command: Command::Reset,
+        },
+    ];
+
+    fn recognizer() -> Recognizer<'static, Command, 2> {
+        Recognizer::new(GESTURES, Duration::from_ms(600))
+    }
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ms(ms)
+    }
+
+    #[test]
+    fn shorter_gesture_waits_for_the_timeout() {
+        let mut gestures = recognizer();
+        assert_eq!(None, gestures.update(at(100), Some(Press(1))));
+        assert_eq!(None, gestures.update(at(699), None));
+        assert_eq!(Some(Command::Select), gestures.update(at(700), None));
+        assert_eq!(None, gestures.update(at(1400), None));
+
+        // nothing longer begins with three presses, so no waiting
+        assert_eq!(
+            Some(Command::Invert),
+            gestures.update(at(2000), Some(Press(3)))
+        );
+    }
+
+    #[test]
+    fn click_then_hold() {
+        let mut gestures = recognizer();
+        assert_eq!(None, gestures.update(at(100), Some(Press(1))));
+        assert_eq!(
+            Some(Command::Reset),
+            gestures.update(at(500), Some(HoldStart(0)))
+        );
+        // the rest of the hold doesn't make anything else
+        assert_eq!(
+            None,
+            gestures.update(at(750), Some(Holding(Duration::from_ms(250))))
+        );
+        assert_eq!(
+            None,
+            gestures.update(at(900), Some(Release(Duration::from_ms(400))))
+        );
+        assert_eq!(None, gestures.update(at(2000), None));
+    }
+
+    #[test]
+    fn mismatch_flushes_then_starts_again() {
+        let mut gestures = recognizer();
+        assert_eq!(None, gestures.update(at(100), Some(Press(1))));
+        // one press then three makes nothing, but the one press was a
+        // gesture on its own and the three presses are another
+        assert_eq!(
+            Some(Command::Select),
+            gestures.update(at(500), Some(Press(3)))
+        );
+        assert_eq!(Some(Command::Invert), gestures.update(at(501), None));
+        assert_eq!(None, gestures.update(at(2000), None));
+    }
+}
@@ -3,92 +3,184 @@
 // Please see the file LICENSE in the source
 // distribution of this software for license terms.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 /// For representing buttons
 pub mod button;
+/// For recognizing buttons pressed in combination
+pub mod chord;
+/// For measuring short intervals precisely with the cycle counter
+#[cfg(feature = "board")]
+pub mod cycles;
+/// For filtering switch bounce out of button readings
+pub mod debounce;
+/// For reading a rotary encoder (the knob)
+pub mod encoder;
+/// For errors shared between modules
+pub mod error;
+/// For running async tasks off the system clock
+pub mod executor;
+/// For timestamping pin edges in interrupt handlers
+pub mod exti;
+/// For recognizing sequences of presses and holds
+pub mod gesture;
+/// For repeating held buttons like a keyboard does
+pub mod repeat;
+/// For running periodic tasks at their own rates
+pub mod runner;
+/// For running code at deadlines without hand-rolled bookkeeping
+pub mod scheduler;
 /// For using the system clock to keep track of time in a loop
+#[cfg(feature = "board")]
 pub mod systick;
+/// For representing points in time and the spans between them
+pub mod time;
+/// For resetting the processor if a task hangs
+pub mod watchdog;
 
 /// Represents time in milliseconds
 pub type Milliseconds = u32;
+/// Represents time in microseconds
+pub type Microseconds = u64;
+/// Represents time in nanoseconds
+pub type Nanoseconds = u64;
 
+#[cfg(feature = "board")]
 use f3::hal::gpio::gpioc::PC3;
+#[cfg(feature = "board")]
 use f3::hal::gpio::{Output, PushPull};
+#[cfg(feature = "board")]
 use f3::hal::prelude::*;
 
+#[cfg(feature = "board")]
 pub type Buzzer = PC3<Output<PushPull>>;
 
-use button::{ButtonEvent, Buttons};
+#[cfg(feature = "board")]
+use button::Button;
+#[cfg(feature = "board")]
+use chord::{ChordEvent, Chords};
+#[cfg(feature = "board")]
+use embedded_hal::digital::v2::InputPin;
+#[cfg(feature = "board")]
 use f3::led::{Led, Leds};
+#[cfg(feature = "board")]
+use repeat::{AutoRepeat, RepeatCurve, RepeatEvent};
+#[cfg(feature = "board")]
+use time::{Duration, Instant};
 
 /// Tracks timer state
-pub struct SimpleTimer {
-    start_button: Buttons,
-    time_button: Buttons,
+#[cfg(feature = "board")]
+pub struct SimpleTimer<START, TIME> {
+    start_button: Button<START>,
+    time_button: Button<TIME>,
+    /// Tells pushing both buttons together apart from pushing either
+    chords: Chords<2>,
+    /// Child lock: while set, only pushing both buttons together does
+    /// anything
+    locked: bool,
+    /// Whether the time button is being held on its own
+    time_held: bool,
+    /// Scrolls the time up while the time button is held
+    time_repeat: AutoRepeat,
     /// The last time this updated
-    was: Milliseconds,
+    was: Instant,
     display: CompassDisplay,
     is_running: bool,
-    time_remaining: Milliseconds,
+    time_remaining: Duration,
     /// Length of time each LED represents
-    period: Milliseconds,
+    period: Duration,
     /// How much of the latest period to spend blinking quickly
-    fast_time: Milliseconds,
+    fast_time: Duration,
 }
 
-const LONG_ON: Milliseconds = 1100;
-const LONG_OFF: Milliseconds = 900;
-const SHORT_ON: Milliseconds = 550;
-const SHORT_OFF: Milliseconds = 450;
-const BLINK: Milliseconds = 600;
+#[cfg(feature = "board")]
+const LONG_ON: Duration = Duration::from_ms(1100);
+#[cfg(feature = "board")]
+const LONG_OFF: Duration = Duration::from_ms(900);
+#[cfg(feature = "board")]
+const SHORT_ON: Duration = Duration::from_ms(550);
+#[cfg(feature = "board")]
+const SHORT_OFF: Duration = Duration::from_ms(450);
+#[cfg(feature = "board")]
+const BLINK: Duration = Duration::from_ms(600);
+/// How close together the buttons must be pushed to count as both at once
+#[cfg(feature = "board")]
+const CHORD_WINDOW: Duration = Duration::from_ms(80);
 
-impl SimpleTimer {
+#[cfg(feature = "board")]
+impl<START: InputPin, TIME: InputPin> SimpleTimer<START, TIME> {
     /// Create a new SimpleTimer
     pub fn new(
-        start: Buttons,
-        time: Buttons,
+        start: Button<START>,
+        time: Button<TIME>,
         leds: Leds,
         buzzer: Buzzer,
-        period: Milliseconds,
+        period: Duration,
     ) -> Self {
         Self {
             start_button: start,
             time_button: time,
-            was: 0,
+            chords: Chords::new(CHORD_WINDOW),
+            locked: false,
+            time_held: false,
+            time_repeat: AutoRepeat::new(RepeatCurve::DEFAULT),
+            was: Instant::default(),
             display: CompassDisplay::new(leds, buzzer),
             is_running: false,
-            time_remaining: 0,
+            time_remaining: Duration::ZERO,
             period,
             fast_time: period / 3,
         }
     }
 
     /// Update the state of the SimpleTimer
-    pub fn update(&mut self, now: Milliseconds) {
+    pub fn update(&mut self, now: Instant) {
         if self.is_running {
             self.time_remaining = self
                 .time_remaining
                 // using saturating sub to avoid panic
-                // by using wrapping_sub, this should also work with
-                // non-timer timekeeper
-                .saturating_sub(now.wrapping_sub(self.was));
+                // Instant subtraction is wrapping-aware, so this keeps
+                // working when the clock wraps
+                .saturating_sub(now - self.was);
         }
-        if ButtonEvent::Push == self.start_button.update(now) {
-            self.is_running = !self.is_running;
+        let events = [self.start_button.update(now), self.time_button.update(now)];
+        self.chords.update(now, events);
+        while let Some(event) = self.chords.poll() {
+            match event {
+                // both buttons together toggle the child lock
+                ChordEvent::Together(_) => self.locked = !self.locked,
+                ChordEvent::Release(1) => self.time_held = false,
+                _ if self.locked => (),
+                ChordEvent::Push(0) => self.is_running = !self.is_running,
+                ChordEvent::Push(1) => {
+                    // A tap shorter than the chord window is pushed and
+                    // released in the same update, so count the push here
+                    // rather than leaving it to the repeater, which would
+                    // never see the button down.
+                    self.add_time(false);
+                    self.time_held = events[1].is_pressed();
+                }
+                _ => (),
+            }
         }
-        if ButtonEvent::Push == self.time_button.update(now) {
-            self.add_time();
+        // holding the time button scrolls the time up, faster and faster;
+        // the push itself was counted above
+        if let Some(RepeatEvent::Repeat(_)) = self.time_repeat.update(now, self.time_held) {
+            self.add_time(true);
         }
         self.update_display(now);
         self.was = now;
     }
 
-    /// Add `self.period`ms to self.time_remaining, up to the maximum.
-    fn add_time(&mut self) {
+    /// Add `self.period` to self.time_remaining, up to the maximum. A
+    /// press at the maximum wraps back around to one period; a repeat
+    /// stays there.
+    fn add_time(&mut self, is_repeat: bool) {
         let max = self.period * 8;
         if self.time_remaining >= max {
-            self.time_remaining = self.period;
+            if !is_repeat {
+                self.time_remaining = self.period;
+            }
         } else {
             self.time_remaining += self.period;
             if self.time_remaining > max {
@@ -97,10 +189,10 @@ impl SimpleTimer {
         }
     }
 
-    fn update_display(&mut self, now: Milliseconds) {
+    fn update_display(&mut self, now: Instant) {
         // important for figuring out how fast to blink and whether the
         // division truncated anything
-        let til_next_period = (self.time_remaining % self.period) as usize;
+        let til_next_period = (self.time_remaining % self.period).as_ms() as usize;
         // the number of whole periods remaining
         let whole_periods = (self.time_remaining / self.period) as usize;
 
@@ -108,7 +200,7 @@ impl SimpleTimer {
             // exactly at time and running
             0 if self.is_running => {
                 // if we're just transitioning to a new solid LED
-                if self.time_remaining > 0 {
+                if !self.time_remaining.is_zero() {
                     self.display.update(now, whole_periods - 1, BlinkKind::Slow);
                 } else {
                     // if time is up
@@ -125,7 +217,7 @@ impl SimpleTimer {
             }
 
             // running and with a fast time left on the latest interval
-            x if x <= (self.fast_time as usize) => {
+            x if x <= (self.fast_time.as_ms() as usize) => {
                 self.display.update(now, whole_periods, BlinkKind::Fast);
             }
 
@@ -136,30 +228,32 @@ impl SimpleTimer {
 }
 
 /// A blinking LED
+#[cfg(feature = "board")]
 struct Blinky {
     /// Which led to blink, if any.
     led_idx: Option<usize>,
     /// Whether the led is currently on
     is_on: bool,
     /// How long to stay on when blinking fast
-    fast_on: Milliseconds,
+    fast_on: Duration,
     /// How long to stay off when blinking fast
-    fast_off: Milliseconds,
+    fast_off: Duration,
     /// How long to stay on when blinking slow
-    slow_on: Milliseconds,
+    slow_on: Duration,
     /// How long to stay off when blinking slow
-    slow_off: Milliseconds,
+    slow_off: Duration,
     /// When next to toggle
-    next_toggle: Milliseconds,
+    next_toggle: Instant,
 }
 
+#[cfg(feature = "board")]
 impl Blinky {
     fn new(
         idx: Option<(usize, &mut Leds)>,
-        fast_on: Milliseconds,
-        fast_off: Milliseconds,
-        slow_on: Milliseconds,
-        slow_off: Milliseconds,
+        fast_on: Duration,
+        fast_off: Duration,
+        slow_on: Duration,
+        slow_off: Duration,
     ) -> Blinky {
         Blinky {
             led_idx: idx.map(|(i, l)| {
@@ -171,14 +265,14 @@ impl Blinky {
             fast_off,
             slow_on,
             slow_off,
-            next_toggle: 0,
+            next_toggle: Instant::default(),
         }
     }
 
     /// Blink the last LED of a group
     fn update_seq(
         &mut self,
-        now: Milliseconds,
+        now: Instant,
         leds: &mut Leds,
         led_idx: Option<usize>,
         is_fast: bool,
@@ -235,6 +329,7 @@ impl Blinky {
     }
 }
 
+#[cfg(feature = "board")]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BlinkKind {
     /// Blink fast
@@ -249,6 +344,7 @@ pub enum BlinkKind {
     // Partial,
 }
 
+#[cfg(feature = "board")]
 impl BlinkKind {
     fn to_some(self, n: usize) -> Option<usize> {
         match self {
@@ -259,15 +355,17 @@ impl BlinkKind {
 }
 
 /// Use the ring of 8 LEDs as a display.
+#[cfg(feature = "board")]
 pub struct CompassDisplay {
     leds: Leds,
     buzzer: Buzzer,
-    next_blink: Option<Milliseconds>,
+    next_blink: Option<Instant>,
     blink_on: bool,
     num_on: usize,
     blinky: Blinky,
 }
 
+#[cfg(feature = "board")]
 impl CompassDisplay {
     pub fn new(mut leds: Leds, mut buzzer: Buzzer) -> CompassDisplay {
         Self::set_all(&mut leds, &mut buzzer, true);
@@ -288,7 +386,7 @@ impl CompassDisplay {
     /// * `blink_idx` - Which LED to blink, and how.
     /// # Panics
     /// Will panic if given more than 8 leds to be solid
-    pub fn update(&mut self, now: Milliseconds, solid: usize, blink: BlinkKind) {
+    pub fn update(&mut self, now: Instant, solid: usize, blink: BlinkKind) {
         assert!(solid <= 8, "we only have 8 leds to be solid!");
         // what we do depends on how we're blinking
         // If we're blinking all LEDs, that's all we need to worry about.
@@ -348,7 +446,7 @@ impl CompassDisplay {
         }
         !off
     }
-    fn blink(&mut self, now: Milliseconds) {
+    fn blink(&mut self, now: Instant) {
         match self.next_blink {
             // We've started blinking
             None => {
@@ -360,7 +458,7 @@ impl CompassDisplay {
             Some(next) => self.toggle(next),
         }
     }
-    fn toggle(&mut self, last: Milliseconds) {
+    fn toggle(&mut self, last: Instant) {
         // toggle LEDs and record status
         self.blink_on = Self::set_all(&mut self.leds, &mut self.buzzer, self.blink_on);
         // set time of next toggle
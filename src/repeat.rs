@@ -0,0 +1,198 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::time::{Duration, Instant};
+
+/// How a held button's repeats start and speed up.
+///
+/// After `delay`, repeats come every `start_interval` at first. Each gap is
+/// then `percent` percent of the one before, down to `min_interval`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RepeatCurve {
+    /// How long the button must be held before it starts repeating
+    pub delay: Duration,
+    /// The gap between the first two repeats
+    pub start_interval: Duration,
+    /// The shortest gap the repeats speed up to
+    pub min_interval: Duration,
+    /// How long each gap is compared to the one before, in percent. 100
+    /// repeats at a steady rate.
+    pub percent: u8,
+}
+
+impl RepeatCurve {
+    /// Starts slowly enough to stop on the value you want, and speeds up
+    /// to about 20 repeats per second after a couple of seconds
+    pub const DEFAULT: RepeatCurve = RepeatCurve {
+        delay: Duration::from_ms(500),
+        start_interval: Duration::from_ms(300),
+        min_interval: Duration::from_ms(50),
+        percent: 85,
+    };
+
+    /// Repeat every `interval` after `delay`, without speeding up.
+    pub const fn steady(delay: Duration, interval: Duration) -> Self {
+        RepeatCurve {
+            delay,
+            start_interval: interval,
+            min_interval: interval,
+            percent: 100,
+        }
+    }
+
+    /// The gap after one of `interval`.
+    fn next_interval(&self, interval: Duration) -> Duration {
+        (interval * u32::from(self.percent) / 100).max(self.min_interval)
+    }
+}
+
+impl Default for RepeatCurve {
+    fn default() -> RepeatCurve {
+        RepeatCurve::DEFAULT
+    }
+}
+
+/// Represents an auto-repeat event.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RepeatEvent {
+    /// Button pressed
+    Press,
+    /// Button still held; a synthetic press. u16 is which repeat this is,
+    /// starting from 1.
+    Repeat(u16),
+}
+
+/// Turns holding a button into a stream of presses, like holding a key on
+/// a keyboard.
+///
+/// Feed it whether the (debounced) button is pressed, e.g. from
+/// `ButtonEvent::is_pressed` or `FancyButton::is_pressed`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::repeat::{AutoRepeat, RepeatCurve};
+/// # use timer::button::Button;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run<P>(mut systick: Systick, mut button: Button<P>) -> !
+/// # where
+/// #     P: embedded_hal::digital::v2::InputPin,
+/// # {
+///
+/// let mut repeat = AutoRepeat::new(RepeatCurve::DEFAULT);
+/// let mut value = 0;
+///
+/// loop {
+///     let now = systick.now();
+///     let pressed = button.update(now).is_pressed();
+///     // count up once per press, and faster and faster while held
+///     if repeat.update(now, pressed).is_some() {
+///         value += 1;
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct AutoRepeat {
+    curve: RepeatCurve,
+    /// When the next repeat is due, while the button is held
+    next: Option<Instant>,
+    /// The gap until the repeat after the next one
+    interval: Duration,
+    /// Repeats so far in this hold
+    count: u16,
+}
+
+impl AutoRepeat {
+    /// Create an `AutoRepeat` that speeds up according to `curve`.
+    pub fn new(curve: RepeatCurve) -> Self {
+        AutoRepeat {
+            curve,
+            next: None,
+            interval: curve.start_interval,
+            count: 0,
+        }
+    }
+
+    /// The curve currently in use.
+    pub fn curve(&self) -> RepeatCurve {
+        self.curve
+    }
+
+    /// Change the curve. Takes effect from the next press.
+    pub fn set_curve(&mut self, curve: RepeatCurve) {
+        self.curve = curve;
+    }
+
+    /// Move on to `now`, with the button `pressed` or not.
+    ///
+    /// # Return
+    /// The press or repeat due, if any. If the caller falls behind, missed
+    /// repeats come one per call until it catches up.
+    pub fn update(&mut self, now: Instant, pressed: bool) -> Option<RepeatEvent> {
+        match (self.next, pressed) {
+            // button has just been pressed
+            (None, true) => {
+                self.next = Some(now + self.curve.delay);
+                self.interval = self.curve.start_interval;
+                self.count = 0;
+                Some(RepeatEvent::Press)
+            }
+            // button has been held long enough to repeat
+            (Some(next), true) if now >= next => {
+                self.next = Some(next + self.interval);
+                self.interval = self.curve.next_interval(self.interval);
+                self.count = self.count.saturating_add(1);
+                Some(RepeatEvent::Repeat(self.count))
+            }
+            // button is still held, or still not
+            (Some(_), true) | (None, false) => None,
+            // button has been released
+            (Some(_), false) => {
+                self.next = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_speed_up_to_the_minimum() {
+        let curve = RepeatCurve::DEFAULT;
+        let mut repeat = AutoRepeat::new(curve);
+        let mut repeats = Vec::new();
+        for ms in 0..5000 {
+            let pressed = (100..4100).contains(&ms);
+            match repeat.update(Instant::from_ms(ms), pressed) {
+                Some(RepeatEvent::Press) => assert_eq!(100, ms),
+                Some(RepeatEvent::Repeat(n)) => {
+                    repeats.push(ms);
+                    assert_eq!(repeats.len(), usize::from(n));
+                }
+                None => (),
+            }
+        }
+
+        assert_eq!(100 + curve.delay.as_ms(), repeats[0]);
+        let gaps: Vec<u32> = repeats.windows(2).map(|w| w[1] - w[0]).collect();
+        assert_eq!(curve.start_interval.as_ms(), gaps[0]);
+        // each gap is shorter than the one before until they reach the
+        // minimum, and none go below it
+        let min = curve.min_interval.as_ms();
+        assert!(gaps.windows(2).all(|w| w[1] < w[0] || w[1] == min));
+        assert!(gaps.iter().all(|&gap| gap >= min));
+        assert_eq!(Some(&min), gaps.last());
+        // and they stop when the button is let go of
+        assert!(4100 - repeats.last().unwrap() <= min);
+    }
+}
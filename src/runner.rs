@@ -0,0 +1,183 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::error::Full;
+use super::scheduler::{Handle, Scheduler};
+use super::time::{Duration, Instant};
+
+/// Refers to a task registered with a `Runner`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TaskHandle(usize);
+
+/// A periodic task's deadline, and how it's been keeping up.
+#[derive(Clone, Copy)]
+struct Task {
+    /// When the task is next due, in the `Runner`'s `Scheduler`
+    deadline: Handle,
+    /// How often the task runs
+    period: Duration,
+    /// How many times the task has been due and not run
+    overruns: u32,
+}
+
+/// Runs up to `N` periodic tasks, each with its own period and phase
+/// offset, in rate-monotonic priority order: whenever several tasks are
+/// due at once, the one with the shortest period runs first (ties go to
+/// whichever was added first).
+///
+/// A task that falls so far behind that it's due again before it has run
+/// skips the releases it missed rather than running several times in a
+/// row, and each one it skips is counted as an overrun.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::runner::Runner;
+/// use timer::time::Duration;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run(mut systick: Systick) -> ! {
+///
+/// let mut runner: Runner<2> = Runner::new(systick.now());
+/// let fast = runner.add(Duration::from_ms(6), Duration::ZERO).unwrap();
+/// let slow = runner
+///     .add(Duration::from_ms(1000), Duration::from_ms(500))
+///     .unwrap();
+///
+/// loop {
+///     while let Some(task) = runner.poll(systick.now()) {
+///         if task == fast {
+///             // runs every 6 ms
+///         } else if task == slow {
+///             // runs every second, half a second after `fast` starts
+///         }
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Runner<const N: usize> {
+    /// When phase offsets are counted from
+    start: Instant,
+    schedule: Scheduler<N>,
+    tasks: [Option<Task>; N],
+}
+
+impl<const N: usize> Runner<N> {
+    /// Create a `Runner` with no tasks, which counts phase offsets from
+    /// `start`.
+    pub fn new(start: Instant) -> Self {
+        Runner {
+            start,
+            schedule: Scheduler::new(),
+            tasks: [None; N],
+        }
+    }
+
+    /// Register a task that first runs `phase` after the start, then every
+    /// `period` after that.
+    ///
+    /// # Panics
+    /// Will panic if `period` is zero.
+    pub fn add(&mut self, period: Duration, phase: Duration) -> Result<TaskHandle, Full> {
+        let idx = self.tasks.iter().position(Option::is_none).ok_or(Full)?;
+        let deadline = self.schedule.every(self.start + phase, period)?;
+        self.tasks[idx] = Some(Task {
+            deadline,
+            period,
+            overruns: 0,
+        });
+        Ok(TaskHandle(idx))
+    }
+
+    /// Returns the highest-priority task due by `now`, and moves it on to
+    /// its next release. Call repeatedly until it returns `None` to run
+    /// every task that's due.
+    pub fn poll(&mut self, now: Instant) -> Option<TaskHandle> {
+        let schedule = &self.schedule;
+        // `min_by_key` keeps the first of equals, so ties go to the task
+        // added first
+        let idx = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, task)| task.map(|t| (idx, t)))
+            .filter(|(_, t)| schedule.deadline(t.deadline).is_some_and(|at| at <= now))
+            .min_by_key(|(_, t)| t.period)
+            .map(|(idx, _)| idx)?;
+
+        let task = self.tasks[idx].as_mut()?;
+        // skip (and count) any releases we've missed entirely
+        task.overruns += self.schedule.catch_up(task.deadline, now).unwrap_or(0);
+        Some(TaskHandle(idx))
+    }
+
+    /// The number of releases of `task` that were skipped because it fell
+    /// behind.
+    pub fn overruns(&self, task: TaskHandle) -> u32 {
+        self.task(task).map_or(0, |t| t.overruns)
+    }
+
+    /// When `task` is next due.
+    pub fn next_due(&self, task: TaskHandle) -> Option<Instant> {
+        self.task(task)
+            .and_then(|t| self.schedule.deadline(t.deadline))
+    }
+
+    /// Clears every task's overrun count.
+    pub fn reset_overruns(&mut self) {
+        for task in self.tasks.iter_mut().flatten() {
+            task.overruns = 0;
+        }
+    }
+
+    /// The task `task` refers to, if there is one.
+    fn task(&self, task: TaskHandle) -> Option<&Task> {
+        self.tasks.get(task.0).and_then(Option::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::{ManualClock, TimeSource};
+
+    #[test]
+    fn shortest_period_runs_first() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut runner: Runner<3> = Runner::new(clock.now());
+        let slow = runner.add(Duration::from_ms(100), Duration::ZERO).unwrap();
+        let fast = runner.add(Duration::from_ms(10), Duration::ZERO).unwrap();
+        let also_fast = runner.add(Duration::from_ms(10), Duration::ZERO).unwrap();
+
+        assert_eq!(Some(fast), runner.poll(clock.now()));
+        assert_eq!(Some(also_fast), runner.poll(clock.now()));
+        assert_eq!(Some(slow), runner.poll(clock.now()));
+        assert_eq!(None, runner.poll(clock.now()));
+        assert_eq!(Err(Full), runner.add(Duration::from_ms(1), Duration::ZERO));
+    }
+
+    #[test]
+    fn missed_releases_are_skipped_and_counted() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut runner: Runner<1> = Runner::new(clock.now());
+        let task = runner
+            .add(Duration::from_ms(10), Duration::from_ms(5))
+            .unwrap();
+
+        clock.advance(Duration::from_ms(37));
+        assert_eq!(Some(task), runner.poll(clock.now()));
+        assert_eq!(None, runner.poll(clock.now()));
+        // due at 5, 15, 25 and 35; only the last one ran
+        assert_eq!(3, runner.overruns(task));
+        assert_eq!(Some(Instant::from_ms(45)), runner.next_due(task));
+
+        runner.reset_overruns();
+        assert_eq!(0, runner.overruns(task));
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::time::{Duration, Instant};
+
+pub use super::error::Full;
+
+/// Refers to a deadline registered with a `Scheduler`. Stays valid until
+/// the deadline is cancelled or, if it is a one-shot, has expired; after
+/// that the `Scheduler` ignores it, even if its slot has been reused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Handle {
+    /// Which slot the deadline lives in
+    slot: usize,
+    /// Which use of the slot this refers to
+    generation: u16,
+}
+
+/// A single deadline slot.
+#[derive(Clone, Copy)]
+struct Slot {
+    /// When the deadline expires
+    at: Instant,
+    /// How often it repeats, if it's periodic
+    period: Option<Duration>,
+    /// Bumped every time the slot is reused, to invalidate old handles
+    generation: u16,
+    /// `false` if the slot is free
+    active: bool,
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        at: Instant::from_ms(0),
+        period: None,
+        generation: 0,
+        active: false,
+    };
+}
+
+/// A fixed-capacity set of one-shot and periodic deadlines.
+///
+/// Call `poll` with the current time after each `Systick` tick to collect
+/// the handles of deadlines that have expired.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::scheduler::Scheduler;
+/// use timer::time::Duration;
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run(mut systick: Systick) -> ! {
+///
+/// let mut schedule: Scheduler<4> = Scheduler::new();
+/// let blink = schedule.every(systick.now(), Duration::from_ms(500)).unwrap();
+/// let beep = schedule.after(systick.now(), Duration::from_secs(3)).unwrap();
+///
+/// loop {
+///     while let Some(expired) = schedule.poll(systick.now()) {
+///         if expired == blink {
+///             // toggle an LED
+///         } else if expired == beep {
+///             // sound the buzzer
+///         }
+///     }
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Scheduler<const N: usize> {
+    slots: [Slot; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    /// Create a `Scheduler` with no deadlines.
+    pub fn new() -> Self {
+        Scheduler {
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Register a deadline that expires once, at `when`.
+    pub fn at(&mut self, when: Instant) -> Result<Handle, Full> {
+        self.insert(when, None)
+    }
+
+    /// Register a deadline that expires once, `delay` after `now`.
+    pub fn after(&mut self, now: Instant, delay: Duration) -> Result<Handle, Full> {
+        self.insert(now + delay, None)
+    }
+
+    /// Register a deadline that first expires at `first` and then every
+    /// `period` after that until cancelled.
+    ///
+    /// # Panics
+    /// Will panic if `period` is zero.
+    pub fn every(&mut self, first: Instant, period: Duration) -> Result<Handle, Full> {
+        assert!(!period.is_zero(), "a periodic deadline needs a period");
+        self.insert(first, Some(period))
+    }
+
+    /// Remove a deadline.
+    ///
+    /// # Return
+    /// `true` if the deadline was still registered
+    pub fn cancel(&mut self, handle: Handle) -> bool {
+        match self.slot_mut(handle) {
+            Some(slot) => {
+                slot.active = false;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move a deadline to `when`. A periodic deadline carries on with the
+    /// same period from its new time.
+    ///
+    /// # Return
+    /// `true` if the deadline was still registered
+    pub fn reschedule(&mut self, handle: Handle, when: Instant) -> bool {
+        match self.slot_mut(handle) {
+            Some(slot) => {
+                slot.at = when;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Push a deadline back by `by`.
+    ///
+    /// # Return
+    /// `true` if the deadline was still registered
+    pub fn delay(&mut self, handle: Handle, by: Duration) -> bool {
+        match self.slot_mut(handle) {
+            Some(slot) => {
+                slot.at += by;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// When a deadline next expires, or `None` if it isn't registered.
+    pub fn deadline(&self, handle: Handle) -> Option<Instant> {
+        self.slots
+            .get(handle.slot)
+            .filter(|slot| slot.active && slot.generation == handle.generation)
+            .map(|slot| slot.at)
+    }
+
+    /// The earliest time any deadline expires, if there are any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.active)
+            .map(|slot| slot.at)
+            .fold(None, |earliest, at| match earliest {
+                Some(e) if e <= at => Some(e),
+                _ => Some(at),
+            })
+    }
+
+    /// Collect the earliest deadline that has expired by `now`. Call
+    /// repeatedly until it returns `None` to collect all of them.
+    ///
+    /// A one-shot deadline is removed when it's collected; a periodic one
+    /// is moved on by one period, so it will be collected again (once per
+    /// missed period) if it has fallen behind.
+    pub fn poll(&mut self, now: Instant) -> Option<Handle> {
+        let mut earliest: Option<usize> = None;
+        for (idx, slot) in self.slots.iter().enumerate() {
+            if !slot.active || slot.at > now {
+                continue;
+            }
+            match earliest {
+                Some(e) if self.slots[e].at <= slot.at => (),
+                _ => earliest = Some(idx),
+            }
+        }
+
+        earliest.map(|idx| {
+            let slot = &mut self.slots[idx];
+            match slot.period {
+                Some(period) => slot.at += period,
+                None => slot.active = false,
+            }
+            Handle {
+                slot: idx,
+                generation: slot.generation,
+            }
+        })
+    }
+
+    /// Collect a periodic deadline that has expired by `now` without
+    /// collecting each period it has fallen behind by: it's moved straight
+    /// on to its first expiry after `now`.
+    ///
+    /// # Return
+    /// How many expiries were skipped, or `None` if the deadline isn't
+    /// registered or isn't periodic
+    pub fn catch_up(&mut self, handle: Handle, now: Instant) -> Option<u32> {
+        let slot = self.slot_mut(handle)?;
+        let period = slot.period?;
+        if slot.at > now {
+            return Some(0);
+        }
+        let missed = (now - slot.at) / period;
+        slot.at += period * (missed + 1);
+        Some(missed)
+    }
+
+    /// Put a new deadline in the first free slot.
+    fn insert(&mut self, at: Instant, period: Option<Duration>) -> Result<Handle, Full> {
+        let (idx, slot) = self
+            .slots
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| !slot.active)
+            .ok_or(Full)?;
+        slot.at = at;
+        slot.period = period;
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.active = true;
+        Ok(Handle {
+            slot: idx,
+            generation: slot.generation,
+        })
+    }
+
+    /// The slot `handle` refers to, if it's still registered.
+    fn slot_mut(&mut self, handle: Handle) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(handle.slot)
+            .filter(|slot| slot.active && slot.generation == handle.generation)
+    }
+}
+
+impl<const N: usize> Default for Scheduler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::{ManualClock, TimeSource};
+
+    #[test]
+    fn deadlines_expire_on_time() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut schedule: Scheduler<2> = Scheduler::new();
+        let blink = schedule.every(clock.now(), Duration::from_ms(500)).unwrap();
+        let beep = schedule
+            .after(clock.now(), Duration::from_ms(1200))
+            .unwrap();
+
+        let (mut blinks, mut beeps) = (0, 0);
+        while clock.now() < Instant::from_ms(2000) {
+            while let Some(expired) = schedule.poll(clock.now()) {
+                if expired == blink {
+                    assert_eq!(Instant::from_ms(500 * blinks), clock.now());
+                    blinks += 1;
+                } else if expired == beep {
+                    assert_eq!(Instant::from_ms(1200), clock.now());
+                    beeps += 1;
+                }
+            }
+            clock.advance(Duration::from_ms(1));
+        }
+        assert_eq!((4, 1), (blinks, beeps));
+        assert_eq!(None, schedule.deadline(beep));
+        assert_eq!(Some(Instant::from_ms(2000)), schedule.deadline(blink));
+    }
+
+    #[test]
+    fn periodic_deadline_catches_up() {
+        let clock = ManualClock::new(Instant::from_ms(100));
+        let mut schedule: Scheduler<1> = Scheduler::new();
+        let tick = schedule.every(clock.now(), Duration::from_ms(10)).unwrap();
+
+        clock.advance(Duration::from_ms(35));
+        let mut collected = 0;
+        while let Some(expired) = schedule.poll(clock.now()) {
+            assert_eq!(tick, expired);
+            collected += 1;
+        }
+        assert_eq!(4, collected);
+        assert_eq!(Some(Instant::from_ms(140)), schedule.deadline(tick));
+    }
+
+    #[test]
+    fn deadlines_cross_the_wrap() {
+        let clock = ManualClock::new(Instant::from_ms(u32::MAX - 5));
+        let mut schedule: Scheduler<1> = Scheduler::new();
+        let beep = schedule.after(clock.now(), Duration::from_ms(10)).unwrap();
+
+        clock.advance(Duration::from_ms(9));
+        assert_eq!(None, schedule.poll(clock.now()));
+        clock.advance(Duration::from_ms(1));
+        assert_eq!(Instant::from_ms(4), clock.now());
+        assert_eq!(Some(beep), schedule.poll(clock.now()));
+        assert_eq!(None, schedule.poll(clock.now()));
+    }
+
+    #[test]
+    fn stale_handles_are_ignored() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut schedule: Scheduler<1> = Scheduler::new();
+        let old = schedule.after(clock.now(), Duration::from_ms(5)).unwrap();
+        assert!(schedule.cancel(old));
+        let new = schedule.after(clock.now(), Duration::from_ms(5)).unwrap();
+
+        assert!(!schedule.delay(old, Duration::from_ms(5)));
+        assert_eq!(Some(Instant::from_ms(5)), schedule.deadline(new));
+        assert_eq!(Err(Full), schedule.at(clock.now()));
+    }
+}
@@ -3,15 +3,257 @@
 // Please see the file LICENSE in the source
 // distribution of this software for license terms.
 
-use super::Milliseconds;
+use super::time::{Duration, Instant, TimeSource};
+use super::Microseconds;
+
+use core::cell::Cell;
 
 // use cast::u32;
+use cortex_m::asm;
+use cortex_m::interrupt::{self, Mutex};
 use cortex_m::peripheral::syst::SystClkSource;
-use cortex_m::peripheral::SYST;
+use cortex_m::peripheral::{SCB, SYST};
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::timer::{CountDown, Periodic};
+use void::Void;
 
 use stm32f30x_hal::rcc::Clocks;
 // use stm32f30x_hal::time::Hertz;
 
+/// Timekeeping shared with the SysTick exception handler.
+#[derive(Clone, Copy)]
+struct Shared {
+    /// The current time since initialization, advanced by `tick()`
+    currently: Instant,
+    /// The length of the tick in progress. Zero until interrupts are
+    /// enabled.
+    period: Duration,
+    /// The length of the tick after this one. Longer than `nominal` if
+    /// `Systick::sleep_until` has stretched it.
+    next: Duration,
+    /// The length of a tick when nothing has stretched it
+    nominal: Duration,
+    /// SysTick clocks per ms
+    clocks_per_ms: u32,
+}
+
+static SHARED: Mutex<Cell<Shared>> = Mutex::new(Cell::new(Shared {
+    currently: Instant::from_ms(0),
+    period: Duration::ZERO,
+    next: Duration::ZERO,
+    nominal: Duration::ZERO,
+    clocks_per_ms: 1,
+}));
+
+/// How many SysTick clocks before the end of a tick it's too late to
+/// stretch the tick that follows it
+const STRETCH_MARGIN: u32 = 64;
+
+/// The longest a single tick can be, in SysTick clocks
+const MAX_TICK_CLOCKS: u32 = 0x0100_0000;
+
+/// Advances the shared clock by one tick. Call this from the `SysTick`
+/// exception handler once `Systick::enable_interrupt` has been called.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cortex_m_rt::exception;
+///
+/// #[exception]
+/// fn SysTick() {
+///     timer::systick::tick();
+/// }
+/// ```
+pub fn tick() {
+    interrupt::free(|cs| {
+        let shared = SHARED.borrow(cs);
+        let mut state = shared.get();
+        let stretched = state.next != state.nominal;
+        state.currently += state.period;
+        state.period = state.next;
+        state.next = state.nominal;
+        if stretched {
+            // The counter has just loaded the stretched reload value, so
+            // put the usual one back for the tick after.
+            // This is safe because the only other writer,
+            // `Systick::sleep_until`, runs with interrupts disabled.
+            unsafe {
+                (*SYST::PTR)
+                    .rvr
+                    .write(reload_for(state.nominal, state.clocks_per_ms))
+            }
+        }
+        shared.set(state);
+    });
+}
+
+/// Returns time since init, as counted by the SysTick exception handler.
+/// Safe to call from the main loop or from other interrupts. Always 0 if
+/// `Systick::enable_interrupt` hasn't been called.
+pub fn now() -> Instant {
+    interrupt::free(|cs| SHARED.borrow(cs).get().currently)
+}
+
+/// The clock kept by the SysTick exception handler, as a `TimeSource`
+/// that can be shared without access to the `Systick` itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SharedClock;
+
+impl TimeSource for SharedClock {
+    fn now(&self) -> Instant {
+        now()
+    }
+}
+
+/// Returns time since init in µs, as counted by the SysTick exception
+/// handler plus however far the counter has got through the current tick.
+/// Safe to call from the main loop or from other interrupts. Always 0 if
+/// `Systick::enable_interrupt` hasn't been called.
+pub fn now_us() -> Microseconds {
+    let (start, elapsed, clocks_per_ms) = position();
+    to_us(start, elapsed, clocks_per_ms)
+}
+
+/// Returns the start of the current tick according to the exception
+/// handler's count, how many SysTick clocks have gone by since the tick
+/// started, and how many SysTick clocks make up a ms.
+fn position() -> (Instant, u32, u32) {
+    interrupt::free(|cs| {
+        let state = SHARED.borrow(cs).get();
+        let mut current = SYST::get_current();
+        let mut start = state.currently;
+        let mut len = state.period;
+        // If the counter wrapped before we could read it, the exception is
+        // still pending (interrupts are off), so count its tick ourselves.
+        // Re-read the counter in case it wrapped after the first read.
+        if SCB::is_pendst_pending() {
+            current = SYST::get_current();
+            start += state.period;
+            len = state.next;
+        }
+        let reload = reload_for(len, state.clocks_per_ms);
+        (start, elapsed(reload, current), state.clocks_per_ms)
+    })
+}
+
+/// The SysTick reload value for a tick of `len`.
+fn reload_for(len: Duration, clocks_per_ms: u32) -> u32 {
+    // a tick is reload + 1 clocks long
+    (len.as_ms() * clocks_per_ms).saturating_sub(1)
+}
+
+/// The number of SysTick clocks since the counter last wrapped.
+fn elapsed(reload: u32, current: u32) -> u32 {
+    // the counter counts down from `reload` to 0
+    reload - current.min(reload)
+}
+
+/// Converts a number of SysTick clocks to µs.
+fn clocks_to_us(clocks: u64, clocks_per_ms: u32) -> Microseconds {
+    clocks * 1000 / u64::from(clocks_per_ms)
+}
+
+/// Combines the start of a tick with how far into the tick the counter is.
+///
+/// # Params
+/// * `start` - The time at the start of the current tick.
+/// * `elapsed` - The number of SysTick clocks since the tick started.
+/// * `clocks_per_ms` - The number of SysTick clocks in a ms.
+fn to_us(start: Instant, elapsed: u32, clocks_per_ms: u32) -> Microseconds {
+    u64::from(start.as_ms()) * 1000 + clocks_to_us(u64::from(elapsed), clocks_per_ms)
+}
+
+/// Which clock drives the SysTick counter.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TickSource {
+    /// The processor clock (HCLK). Gives the finest resolution.
+    Core,
+    /// The external reference clock, HCLK/8. Allows ticks 8 times as long
+    /// as `Core` does.
+    External,
+    /// `Core` if the requested period fits, `External` if it doesn't.
+    Auto,
+}
+
+impl TickSource {
+    /// The SysTick counter frequency in Hz, or `None` for `Auto`.
+    fn hz(self, clocks: &Clocks) -> Option<u32> {
+        match self {
+            TickSource::Core => Some(clocks.hclk().0),
+            TickSource::External => Some(clocks.hclk().0 / 8),
+            TickSource::Auto => None,
+        }
+    }
+}
+
+/// Where µs timestamps from `now_us()` wrap around, since they're built on
+/// a ms count that wraps at 2^32
+const US_WRAP: Microseconds = (1 << 32) * 1000;
+
+/// The number of µs from `earlier` to `later`, allowing for `later` having
+/// wrapped.
+fn us_since(earlier: Microseconds, later: Microseconds) -> Microseconds {
+    (later + US_WRAP - earlier) % US_WRAP
+}
+
+/// Why `Systick::new` couldn't use the requested tick length.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PeriodError {
+    /// The period is too short for the SysTick clock to count. `min` and
+    /// `max` are the shortest and longest valid periods.
+    TooShort { min: Duration, max: Duration },
+    /// The period is too long for the 24-bit SysTick counter. `min` and
+    /// `max` are the shortest and longest valid periods.
+    TooLong { min: Duration, max: Duration },
+}
+
+impl PeriodError {
+    /// The shortest and longest periods that would have worked.
+    pub fn valid_range(self) -> (Duration, Duration) {
+        match self {
+            PeriodError::TooShort { min, max } | PeriodError::TooLong { min, max } => (min, max),
+        }
+    }
+}
+
+/// Loop timing statistics gathered by `Systick::wait_til_wrapped`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LoopStats {
+    /// How many times the loop was still busy when its tick ended
+    pub overruns: u32,
+    /// How many ticks went by entirely without the loop waiting for them.
+    /// Only exact with interrupts enabled; see `unmeasured`.
+    pub missed_ticks: u32,
+    /// The furthest the loop has run past the end of a tick, in µs. Only
+    /// exact with interrupts enabled; see `unmeasured`.
+    pub max_lateness: Microseconds,
+    /// How many of the overruns happened without interrupts enabled, so
+    /// their length is unknown. The counter can only show that it wrapped,
+    /// not how often, so each of these may have lost any number of ticks
+    /// that neither `missed_ticks`, `max_lateness` nor `Systick::now()`
+    /// account for. Always 0 after `Systick::enable_interrupt`.
+    pub unmeasured: u32,
+    /// Time spent asleep in `Systick::sleep_til_wrapped`, in µs
+    pub asleep: Microseconds,
+    /// SysTick clocks spent outside `Systick::wait_til_wrapped`
+    busy: u64,
+    /// SysTick clocks covered by these statistics
+    total: u64,
+}
+
+impl LoopStats {
+    /// The percentage of time spent outside `Systick::wait_til_wrapped`.
+    pub fn utilization(&self) -> u8 {
+        if 0 == self.total {
+            0
+        } else {
+            (self.busy * 100 / self.total).min(100) as u8
+        }
+    }
+}
+
 // this code is modified from
 // https://docs.rs/stm32f30x-hal/0.2.0/src/stm32f30x_hal/delay.rs.html#11-14
 /// System timer (SysTick) as a delay provider and clock
@@ -20,6 +262,7 @@ use stm32f30x_hal::rcc::Clocks;
 ///
 /// ```no_run
 /// use timer::systick::Systick;
+/// use timer::time::Duration;
 ///
 /// use f3::hal::prelude::*;
 /// use f3::hal::stm32f30x;
@@ -36,12 +279,12 @@ use stm32f30x_hal::rcc::Clocks;
 ///     // set up system timer using default settings of 8 MHz
 ///     let hal_clocks = rcc.cfgr.freeze(&mut flash.acr);
 ///     // set up systick clock with period of 6ms
-///     let mut systick = Systick::new(cp.SYST, hal_clocks, 6).unwrap();
+///     let mut systick = Systick::new(cp.SYST, hal_clocks, Duration::from_ms(6)).unwrap();
 ///
 ///     loop {
 ///         iprintln!(
 ///             "{}ms have passed since timer initialization",
-///             systick.now()
+///             systick.now().as_ms()
 ///             );
 ///
 ///         systick.wait_til_wrapped();
@@ -51,62 +294,448 @@ use stm32f30x_hal::rcc::Clocks;
 pub struct Systick {
     /// Contains clock frequencies
     _clocks: Clocks,
+    /// Which clock drives the counter. Never `Auto`.
+    source: TickSource,
     /// The system timer.
     syst: SYST,
-    /// The length of a single tick, in ms. Can range from 1 to 0x00ff_ffff
-    period: u32,
-    /// The current time since initialization in ms, accurate to `period`
-    /// ms. Note: Only accurate if self.wait_til_wrapped is called
+    /// The length of a single tick. Within `Systick::period_range` for
+    /// `source`
+    period: Duration,
+    /// SysTick clocks per ms
+    clocks_per_ms: u32,
+    /// The current time since initialization, accurate to `period`.
+    /// Note: Only accurate if self.wait_til_wrapped or self.poll is called
     /// frequently enough!
-    currently: Milliseconds,
+    currently: Instant,
+    /// `true` if the SysTick exception handler keeps time instead
+    interrupt: bool,
+    /// The start of the tick in which `self.wait_til_wrapped()` last
+    /// returned
+    waited: Instant,
+    /// Loop timing statistics
+    stats: LoopStats,
 }
 
 impl Systick {
-    /// Configures the system timer (SysTick) as a tick provider
+    /// Configures the system timer (SysTick) as a tick provider, driven by
+    /// the processor clock unless `period` is too long for it, in which
+    /// case the slower external reference clock is used.
     ///
     /// # Arguments
     ///
-    /// * `period` - The number of milliseconds that make up a timer
-    /// 'tick'.
-    pub fn new(mut syst: SYST, clocks: Clocks, period: Milliseconds) -> Option<Self> {
-        syst.set_clock_source(SystClkSource::Core);
-
-        // convert ms to ticks
-        #[allow(clippy::cast_lossless)] // no way this'll ever change
-        let ticks = (clocks.sysclk().0 as u64 * period as u64) / 1000 - 1; // change ms to clockticks
+    /// * `period` - The length of a timer 'tick'. Must be within
+    /// `Systick::period_range(&clocks, TickSource::Auto)`.
+    pub fn new(syst: SYST, clocks: Clocks, period: Duration) -> Result<Self, PeriodError> {
+        Self::with_source(syst, clocks, period, TickSource::Auto)
+    }
 
+    /// Configures the system timer (SysTick) as a tick provider, driven by
+    /// the clock `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - The length of a timer 'tick'. Must be within
+    /// `Systick::period_range(&clocks, source)`.
+    /// * `source` - Which clock drives the counter.
+    pub fn with_source(
+        mut syst: SYST,
+        clocks: Clocks,
+        period: Duration,
+        source: TickSource,
+    ) -> Result<Self, PeriodError> {
         // check to see if in range
-        if ticks >= 1 && ticks <= 0x00ff_ffff {
+        let (min, max) = Self::period_range(&clocks, source);
+        if period < min {
+            Err(PeriodError::TooShort { min, max })
+        } else if period > max {
+            Err(PeriodError::TooLong { min, max })
+        } else {
+            let source = match source {
+                TickSource::Auto if period > Self::period_range(&clocks, TickSource::Core).1 => {
+                    TickSource::External
+                }
+                TickSource::Auto => TickSource::Core,
+                other => other,
+            };
+            let hz = source.hz(&clocks).unwrap_or(1);
+            syst.set_clock_source(match source {
+                TickSource::External => SystClkSource::External,
+                _ => SystClkSource::Core,
+            });
+
+            // convert ms to ticks
+            let ticks = (u64::from(hz) * u64::from(period.as_ms())) / 1000 - 1; // change ms to clockticks
+
             // set countdown ticks, zero current time, start the timer
             syst.set_reload(ticks as u32);
             syst.clear_current();
             syst.enable_counter();
-            Some(Systick {
+            Ok(Systick {
                 syst,
                 _clocks: clocks,
+                source,
                 period,
-                currently: 0,
+                clocks_per_ms: (ticks as u32 + 1) / period.as_ms(),
+                currently: Instant::from_ms(0),
+                interrupt: false,
+                waited: Instant::from_ms(0),
+                stats: LoopStats::default(),
             })
-        } else {
-            None
         }
     }
 
+    /// Returns the shortest and longest tick lengths `Systick::with_source`
+    /// can use with `clocks` and `source`.
+    pub fn period_range(clocks: &Clocks, source: TickSource) -> (Duration, Duration) {
+        let hz = match source.hz(clocks) {
+            Some(hz) => u64::from(hz),
+            None => {
+                // the fast clock allows the shortest ticks, the slow one
+                // the longest
+                let (min, _) = Self::period_range(clocks, TickSource::Core);
+                let (_, max) = Self::period_range(clocks, TickSource::External);
+                return (min, max);
+            }
+        };
+        // the reload value must be at least 1, so a tick is at least two
+        // clocks long...
+        let min = ((2 * 1000 + hz - 1) / hz).max(1);
+        // ...and at most 0x0100_0000 clocks long
+        let max = (u64::from(MAX_TICK_CLOCKS) * 1000 / hz).min(u64::from(u32::MAX));
+        (Duration::from_ms(min as u32), Duration::from_ms(max as u32))
+    }
+
+    /// Returns which clock drives the counter: `Core` or `External`.
+    pub fn tick_source(&self) -> TickSource {
+        self.source
+    }
+
+    /// Switches to interrupt-driven timekeeping: from now on the clock is
+    /// advanced by the SysTick exception handler, which **must** call
+    /// `systick::tick()`, rather than by `self.wait_til_wrapped()`.
+    pub fn enable_interrupt(&mut self) {
+        if self.interrupt {
+            return;
+        }
+        interrupt::free(|cs| {
+            SHARED.borrow(cs).set(Shared {
+                currently: self.currently,
+                period: self.period,
+                next: self.period,
+                nominal: self.period,
+                clocks_per_ms: self.clocks_per_ms,
+            })
+        });
+        self.interrupt = true;
+        self.syst.enable_interrupt();
+    }
+
     /// Blocks until a tick has occurred since this was last called.
     /// Updates the current time.
+    ///
+    /// If one or more ticks have already gone by since this was last
+    /// called, returns straight away and records the overrun in
+    /// `self.stats()`. Without interrupts, only one wrap can be seen between
+    /// calls to `self.poll()`, so an overrun's length and the ticks lost to
+    /// it aren't known; it's counted in `LoopStats::unmeasured` instead.
     pub fn wait_til_wrapped(&mut self) {
-        while !SYST::has_wrapped(&mut self.syst) {}
-        self.currently += self.period as Milliseconds;
+        self.wait(false);
+    }
+
+    /// Like `self.wait_til_wrapped()`, but sleeps (WFI) until the SysTick
+    /// exception instead of busy-waiting, and adds the time spent asleep to
+    /// `self.stats()`. Interrupts **must** be enabled with
+    /// `self.enable_interrupt()` for the SysTick to wake the processor;
+    /// until then, this busy-waits.
+    pub fn sleep_til_wrapped(&mut self) {
+        self.wait(true);
+    }
+
+    /// Blocks until a tick has occurred since this was last called, by
+    /// sleeping if `sleep` is `true` and interrupts are enabled.
+    fn wait(&mut self, sleep: bool) {
+        let tick = u64::from(self.period.as_ms() * self.clocks_per_ms);
+        let (start, elapsed) = self.position();
+        let behind = (start - self.waited) / self.period;
+
+        if 0 == behind {
+            // we're on time, so wait for the tick to end
+            self.stats.busy += u64::from(elapsed);
+            self.stats.total += tick;
+            if self.interrupt && sleep {
+                while self.sleep_unless_past(start) {}
+            } else if self.interrupt {
+                // wait on the exception handler's count, not the wrap flag,
+                // so we stay in step with the shared clock
+                while now() == start {}
+            } else {
+                while !self.poll() {}
+            }
+        } else {
+            // the tick we were running in has already ended, as have
+            // `behind - 1` more after it
+            let late = u64::from(behind - 1) * tick + u64::from(elapsed);
+            self.stats.overruns += 1;
+            if !self.interrupt {
+                // any wraps after the last poll were folded into one
+                self.stats.unmeasured += 1;
+            }
+            self.stats.missed_ticks += behind - 1;
+            self.stats.max_lateness = self
+                .stats
+                .max_lateness
+                .max(clocks_to_us(late, self.clocks_per_ms));
+            self.stats.busy += tick + late;
+            self.stats.total += tick + late;
+        }
+        self.waited = self.now();
+    }
+
+    /// Sleeps until the next interrupt, unless the shared clock has already
+    /// moved on from `start`.
+    ///
+    /// # Return
+    /// `true` if it slept
+    fn sleep_unless_past(&mut self, start: Instant) -> bool {
+        // With interrupts masked, an exception that arrives between the
+        // check and the WFI still wakes us, rather than being handled first
+        // and leaving us asleep for a whole extra tick.
+        let asleep = interrupt::free(|_| {
+            if now() != start {
+                return None;
+            }
+            let before = now_us();
+            asm::wfi();
+            Some(now_us().saturating_sub(before))
+        });
+        match asleep {
+            Some(us) => {
+                self.stats.asleep += us;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sleeps until `deadline`, stretching ticks so the processor isn't
+    /// woken every `self.tick_len()` in between. `self.now()` stays
+    /// correct throughout; it just advances in bigger steps while asleep.
+    ///
+    /// Wakes at the end of the tick in which `deadline` falls, so the
+    /// deadline may be overshot by up to one tick, just like with
+    /// `self.wait_til_wrapped()`. Interrupts **must** be enabled with
+    /// `self.enable_interrupt()`; until then, this busy-waits tick by
+    /// tick.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use timer::scheduler::Scheduler;
+    /// # use timer::systick::Systick;
+    /// # fn run(mut systick: Systick, mut schedule: Scheduler<4>) -> ! {
+    /// loop {
+    ///     while let Some(_expired) = schedule.poll(systick.now()) {
+    ///         // handle the deadline
+    ///     }
+    ///     match schedule.next_deadline() {
+    ///         Some(next) => systick.sleep_until(next),
+    ///         None => systick.sleep_til_wrapped(),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn sleep_until(&mut self, deadline: Instant) {
+        if !self.interrupt {
+            while self.now() < deadline {
+                self.wait_til_wrapped();
+            }
+            return;
+        }
+        while now() < deadline {
+            match self.stretch_next(deadline) {
+                Some(start) => while self.sleep_unless_past(start) {},
+                // the tick is about to end anyway
+                None => {
+                    self.sleep_unless_past(now());
+                }
+            }
+        }
+        self.waited = now();
+    }
+
+    /// Makes the tick after the current one last until `deadline`, as far
+    /// as the 24-bit reload value allows, but no shorter than usual.
+    ///
+    /// # Return
+    /// The start of the current tick, or `None` if it's too close to its
+    /// end to change the next one.
+    fn stretch_next(&mut self, deadline: Instant) -> Option<Instant> {
+        let longest = Duration::from_ms(MAX_TICK_CLOCKS / self.clocks_per_ms);
+        interrupt::free(|cs| {
+            let shared = SHARED.borrow(cs);
+            let mut state = shared.get();
+            if SCB::is_pendst_pending() || SYST::get_current() < STRETCH_MARGIN {
+                return None;
+            }
+            let end = state.currently + state.period;
+            let next = (deadline - end).min(longest).max(state.nominal);
+            if next != state.next {
+                // takes effect when the counter next wraps
+                self.syst.set_reload(reload_for(next, state.clocks_per_ms));
+                state.next = next;
+                shared.set(state);
+            }
+            Some(state.currently)
+        })
     }
 
-    /// Returns time since init in ms. Doesn't update until
-    /// self.wait_til_wrapped() is called.
-    pub fn now(&self) -> Milliseconds {
-        self.currently
+    /// Checks, without blocking, whether the counter has wrapped, and if so
+    /// updates the current time. Calling this at least once per tick during
+    /// long stretches of work lets the clock keep up; any further wraps
+    /// between two calls are lost.
+    ///
+    /// # Return
+    /// `true` if the counter had wrapped
+    pub fn poll(&mut self) -> bool {
+        if self.interrupt {
+            // the exception handler takes care of it
+            false
+        } else if self.syst.has_wrapped() {
+            self.currently += self.period;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns loop timing statistics gathered since initialization or the
+    /// last call to `self.reset_stats()`.
+    pub fn stats(&self) -> LoopStats {
+        self.stats
     }
 
-    /// Returns the tick length in milliseconds
-    pub fn tick_len(&self) -> Milliseconds {
+    /// Clears loop timing statistics.
+    pub fn reset_stats(&mut self) {
+        self.stats = LoopStats::default();
+    }
+
+    /// Returns time since init. Unless interrupts are enabled, doesn't
+    /// update until self.wait_til_wrapped() is called.
+    pub fn now(&self) -> Instant {
+        if self.interrupt {
+            now()
+        } else {
+            self.currently
+        }
+    }
+
+    /// Returns time since init in µs, including how far through the
+    /// current tick the counter is. Unlike `self.now()`, this is accurate
+    /// to the SysTick clock rather than to the tick length.
+    pub fn now_us(&mut self) -> Microseconds {
+        let (start, elapsed) = self.position();
+        to_us(start, elapsed, self.clocks_per_ms)
+    }
+
+    /// Returns the start of the current tick and how many SysTick clocks
+    /// have gone by since it started.
+    fn position(&mut self) -> (Instant, u32) {
+        if self.interrupt {
+            let (start, elapsed, _) = position();
+            return (start, elapsed);
+        }
+        let reload = SYST::get_reload();
+        let mut current = SYST::get_current();
+        // Re-read the counter in case it wrapped after the first read.
+        if self.poll() {
+            current = SYST::get_current();
+        }
+        (self.currently, elapsed(reload, current))
+    }
+
+    /// Returns the tick length
+    pub fn tick_len(&self) -> Duration {
         self.period
     }
+
+    /// Returns an `embedded_hal` `CountDown` timer that runs off this
+    /// `Systick`'s clock, so the SysTick can keep serving as a clock while
+    /// a driver uses it as a timer.
+    pub fn countdown(&mut self) -> Countdown {
+        Countdown {
+            start: self.now_us(),
+            period: 0,
+            systick: self,
+        }
+    }
+
+    /// Busy-waits for `us` µs, keeping the clock up to date meanwhile.
+    fn delay(&mut self, us: Microseconds) {
+        let start = self.now_us();
+        while us_since(start, self.now_us()) < us {}
+    }
 }
+
+impl TimeSource for Systick {
+    fn now(&self) -> Instant {
+        Systick::now(self)
+    }
+}
+
+/// Implements the `embedded_hal` delay traits for `Systick`
+macro_rules! delay_impls {
+    ($($t:ty),+) => {$(
+        impl DelayMs<$t> for Systick {
+            fn delay_ms(&mut self, ms: $t) {
+                self.delay(u64::from(ms) * 1000);
+            }
+        }
+        impl DelayUs<$t> for Systick {
+            fn delay_us(&mut self, us: $t) {
+                self.delay(u64::from(us));
+            }
+        }
+    )+};
+}
+
+delay_impls!(u8, u16, u32);
+
+/// A periodic `embedded_hal` `CountDown` timer running off a `Systick`.
+/// Made by `Systick::countdown`.
+pub struct Countdown<'a> {
+    systick: &'a mut Systick,
+    /// When the current period started, in µs
+    start: Microseconds,
+    /// The length of a period, in µs
+    period: Microseconds,
+}
+
+impl<'a> Countdown<'a> {
+    /// Returns the time according to the underlying `Systick`.
+    pub fn now(&self) -> Instant {
+        self.systick.now()
+    }
+}
+
+impl<'a> CountDown for Countdown<'a> {
+    type Time = Duration;
+
+    fn start<T: Into<Duration>>(&mut self, count: T) {
+        self.period = u64::from(count.into().as_ms()) * 1000;
+        self.start = self.systick.now_us();
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        let now = self.systick.now_us();
+        if us_since(self.start, now) >= self.period {
+            // count the next period from when this one should have ended,
+            // not from now, so periods don't drift
+            self.start = (self.start + self.period) % US_WRAP;
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<'a> Periodic for Countdown<'a> {}
@@ -0,0 +1,282 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::Milliseconds;
+
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::ops::{Add, AddAssign, Div, Mul, Rem, Sub, SubAssign};
+
+/// Something that can tell the time.
+///
+/// Application logic that asks a `TimeSource` for the time, rather than
+/// asking `Systick` directly, can be driven by a `ManualClock` instead.
+pub trait TimeSource {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// A clock that only moves when told to, for driving time-dependent code
+/// deterministically (in tests, for instance).
+///
+/// # Examples
+///
+/// ```
+/// use timer::time::{Duration, Instant, ManualClock, TimeSource};
+///
+/// let clock = ManualClock::new(Instant::from_ms(0));
+/// clock.advance(Duration::from_ms(250));
+/// assert_eq!(Instant::from_ms(250), clock.now());
+/// ```
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Cell<Instant>,
+}
+
+impl ManualClock {
+    /// Create a `ManualClock` reading `start`.
+    pub fn new(start: Instant) -> Self {
+        ManualClock {
+            now: Cell::new(start),
+        }
+    }
+
+    /// Move the clock forward by `by`. The clock wraps just like a real
+    /// one would.
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+
+    /// Set the clock to read `to`.
+    pub fn set(&self, to: Instant) {
+        self.now.set(to);
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+impl<T: TimeSource> TimeSource for &T {
+    fn now(&self) -> Instant {
+        (*self).now()
+    }
+}
+
+/// A point in time, in ms since some arbitrary start (usually `Systick`
+/// initialization).
+///
+/// The underlying count wraps after ~49.7 days. Comparison and arithmetic
+/// are wrapping-aware, so two `Instant`s compare correctly as long as they
+/// are less than ~24.8 days apart, no matter how long the clock has been
+/// running.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Instant(Milliseconds);
+
+impl Instant {
+    /// Create an `Instant` `ms` milliseconds after the start of the clock.
+    pub const fn from_ms(ms: Milliseconds) -> Self {
+        Instant(ms)
+    }
+
+    /// The raw (wrapping) number of milliseconds since the start of the
+    /// clock.
+    pub fn as_ms(self) -> Milliseconds {
+        self.0
+    }
+
+    /// Returns the time elapsed since `earlier`, or `None` if `earlier` is
+    /// actually later than `self`.
+    pub fn checked_duration_since(self, earlier: Instant) -> Option<Duration> {
+        if self >= earlier {
+            Some(Duration(self.0.wrapping_sub(earlier.0)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the time elapsed since `earlier`, or zero if `earlier` is
+    /// actually later than `self`.
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+}
+
+impl PartialOrd for Instant {
+    /// Compares by the signed (wrapping) difference between the two, so an
+    /// `Instant` just after the wrap is later than one just before it.
+    fn partial_cmp(&self, other: &Instant) -> Option<Ordering> {
+        Some((self.0.wrapping_sub(other.0) as i32).cmp(&0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+    fn sub(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+    /// Same as `self.duration_since(rhs)`: saturates to zero rather than
+    /// panicking if `rhs` is later than `self`.
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+/// A span of time in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Duration(Milliseconds);
+
+impl Duration {
+    /// No time at all
+    pub const ZERO: Duration = Duration(0);
+
+    /// Create a `Duration` of `ms` milliseconds.
+    pub const fn from_ms(ms: Milliseconds) -> Self {
+        Duration(ms)
+    }
+
+    /// Create a `Duration` of `secs` seconds.
+    pub const fn from_secs(secs: u32) -> Self {
+        Duration(secs * 1000)
+    }
+
+    /// The length of this `Duration` in milliseconds.
+    pub fn as_ms(self) -> Milliseconds {
+        self.0
+    }
+
+    /// `true` if this is no time at all
+    pub fn is_zero(self) -> bool {
+        0 == self.0
+    }
+
+    /// Subtract `rhs`, or return `None` if it is longer than `self`.
+    pub fn checked_sub(self, rhs: Duration) -> Option<Duration> {
+        self.0.checked_sub(rhs.0).map(Duration)
+    }
+
+    /// Subtract `rhs`, stopping at zero.
+    pub fn saturating_sub(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Add `rhs`, stopping at the longest representable `Duration`.
+    pub fn saturating_add(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl From<Milliseconds> for Duration {
+    fn from(ms: Milliseconds) -> Duration {
+        Duration(ms)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+    fn add(self, rhs: Duration) -> Duration {
+        Duration(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Mul<u32> for Duration {
+    type Output = Duration;
+    fn mul(self, rhs: u32) -> Duration {
+        Duration(self.0 * rhs)
+    }
+}
+
+impl Div<u32> for Duration {
+    type Output = Duration;
+    fn div(self, rhs: u32) -> Duration {
+        Duration(self.0 / rhs)
+    }
+}
+
+impl Div for Duration {
+    type Output = u32;
+    /// How many whole `rhs` fit in `self`
+    fn div(self, rhs: Duration) -> u32 {
+        self.0 / rhs.0
+    }
+}
+
+impl Rem for Duration {
+    type Output = Duration;
+    /// What's left of `self` after removing as many whole `rhs` as fit
+    fn rem(self, rhs: Duration) -> Duration {
+        Duration(self.0 % rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering_survives_the_wrap() {
+        let start = Instant::from_ms(u32::MAX - 5);
+        let later = start + Duration::from_ms(10);
+        assert_eq!(4, later.as_ms());
+        assert!(later > start);
+        assert!(start < later);
+        assert_eq!(Some(Ordering::Greater), later.partial_cmp(&start));
+    }
+
+    #[test]
+    fn subtraction_survives_the_wrap() {
+        let start = Instant::from_ms(u32::MAX - 5);
+        let later = start + Duration::from_ms(10);
+        assert_eq!(Duration::from_ms(10), later - start);
+        assert_eq!(start, later - Duration::from_ms(10));
+        // the other way round saturates rather than wrapping to ~49 days
+        assert_eq!(Duration::ZERO, start - later);
+        assert_eq!(None, start.checked_duration_since(later));
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright © 2019 Robin Gearn, James Wescott
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use super::error::Full;
+use super::time::{Duration, Instant};
+
+#[cfg(feature = "board")]
+use core::mem::MaybeUninit;
+#[cfg(feature = "board")]
+use core::ptr;
+
+#[cfg(feature = "board")]
+use f3::hal::stm32f30x::{IWDG, RCC};
+
+/// Something that resets the processor unless it's fed regularly.
+pub trait Watchdog {
+    /// Put off the reset.
+    fn feed(&mut self);
+
+    /// Called once when `task` misses its check-in, before the watchdog
+    /// stops being fed, so it can be noted down for after the reset. Does
+    /// nothing by default.
+    fn starving(&mut self, _task: TaskId) {}
+}
+
+/// Refers to a task registered with a `Supervisor`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TaskId(pub u8);
+
+/// When a task last checked in, and how often it has to.
+#[derive(Clone, Copy)]
+struct CheckIn {
+    /// The longest the task may go without checking in
+    interval: Duration,
+    /// When the task last checked in
+    last: Instant,
+}
+
+/// Feeds a `Watchdog` only while every one of up to `N` registered tasks
+/// keeps checking in on time, so a single hung task resets the processor.
+///
+/// Once a task has missed its check-in, the watchdog is never fed again,
+/// even if the task recovers: something went wrong, and the reset should
+/// go ahead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::time::Duration;
+/// use timer::watchdog::{Supervisor, Watchdog};
+/// # struct Systick;
+/// # impl Systick {
+/// #     fn now(&self) -> timer::time::Instant { timer::time::Instant::from_ms(0) }
+/// #     fn wait_til_wrapped(&mut self) {}
+/// # }
+/// # fn run(mut systick: Systick, watchdog: impl Watchdog) -> ! {
+///
+/// let mut supervisor: Supervisor<_, 2> = Supervisor::new(watchdog, systick.now());
+/// let display = supervisor.register(Duration::from_ms(100)).unwrap();
+/// let input = supervisor.register(Duration::from_ms(100)).unwrap();
+///
+/// loop {
+///     // update the display, then
+///     supervisor.check_in(display, systick.now());
+///     // handle input, then
+///     supervisor.check_in(input, systick.now());
+///
+///     supervisor.update(systick.now());
+///     systick.wait_til_wrapped();
+/// }
+/// # }
+/// ```
+pub struct Supervisor<W, const N: usize> {
+    watchdog: W,
+    /// When tasks registered from now on are counted as having checked in
+    started: Instant,
+    tasks: [Option<CheckIn>; N],
+    /// The first task to miss its check-in, if any has
+    starved: Option<TaskId>,
+}
+
+impl<W: Watchdog, const N: usize> Supervisor<W, N> {
+    /// Create a `Supervisor` with no tasks. The watchdog is fed on every
+    /// `update` until tasks are registered.
+    pub fn new(watchdog: W, now: Instant) -> Self {
+        Supervisor {
+            watchdog,
+            started: now,
+            tasks: [None; N],
+            starved: None,
+        }
+    }
+
+    /// Register a task that must check in at least every `interval`. It
+    /// counts as having checked in when the `Supervisor` was created.
+    pub fn register(&mut self, interval: Duration) -> Result<TaskId, Full> {
+        let idx = self.tasks.iter().position(Option::is_none).ok_or(Full)?;
+        self.tasks[idx] = Some(CheckIn {
+            interval,
+            last: self.started,
+        });
+        Ok(TaskId(idx as u8))
+    }
+
+    /// Record that `task` is still alive.
+    pub fn check_in(&mut self, task: TaskId, now: Instant) {
+        if let Some(Some(check_in)) = self.tasks.get_mut(usize::from(task.0)) {
+            check_in.last = now;
+        }
+    }
+
+    /// Feeds the watchdog if every task has checked in on time.
+    ///
+    /// # Return
+    /// The task that missed its check-in, if any has
+    pub fn update(&mut self, now: Instant) -> Option<TaskId> {
+        if self.starved.is_none() {
+            let overdue = self.tasks.iter().position(|task| match task {
+                Some(t) => now - t.last > t.interval,
+                None => false,
+            });
+            match overdue {
+                None => self.watchdog.feed(),
+                Some(idx) => {
+                    let task = TaskId(idx as u8);
+                    self.starved = Some(task);
+                    self.watchdog.starving(task);
+                }
+            }
+        }
+        self.starved
+    }
+
+    /// The first task to miss its check-in, if any has.
+    pub fn starved(&self) -> Option<TaskId> {
+        self.starved
+    }
+
+    /// The watchdog being fed.
+    pub fn watchdog(&mut self) -> &mut W {
+        &mut self.watchdog
+    }
+}
+
+/// Marks `RECORD` as holding a starved task
+#[cfg(feature = "board")]
+const RECORD_MAGIC: u32 = 0x5741_5443;
+
+/// Which task starved, kept in memory that isn't cleared on reset: the
+/// magic number, then the task.
+#[cfg(feature = "board")]
+#[link_section = ".uninit.WATCHDOG_RECORD"]
+static mut RECORD: MaybeUninit<[u32; 2]> = MaybeUninit::uninit();
+
+/// The independent watchdog (IWDG), clocked from the ~40 kHz internal LSI
+/// oscillator. Once started it can't be stopped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use timer::time::{Duration, Instant};
+/// use timer::watchdog::{IndependentWatchdog, Supervisor};
+/// # fn run(iwdg: f3::hal::stm32f30x::IWDG, now: Instant) {
+///
+/// let iwdg = IndependentWatchdog::start(iwdg, Duration::from_ms(500));
+/// let mut supervisor: Supervisor<_, 2> = Supervisor::new(iwdg, now);
+/// # }
+/// ```
+#[cfg(feature = "board")]
+pub struct IndependentWatchdog {
+    iwdg: IWDG,
+}
+
+/// LSI ticks per ms
+#[cfg(feature = "board")]
+const LSI_PER_MS: u32 = 40;
+
+#[cfg(feature = "board")]
+impl IndependentWatchdog {
+    /// Starts the watchdog so it resets the processor if not fed for
+    /// about `timeout`, up to a maximum of about 26 seconds.
+    pub fn start(iwdg: IWDG, timeout: Duration) -> Self {
+        // find the smallest prescaler (4 << pr) that lets the 12-bit
+        // reload value reach the timeout
+        let lsi = timeout.as_ms().saturating_mul(LSI_PER_MS);
+        let mut pr = 0;
+        while pr < 6 && lsi / (4 << pr) > 0x1000 {
+            pr += 1;
+        }
+        let reload = (lsi / (4 << pr)).max(1).min(0x1000) - 1;
+
+        // These are safe because every value written is within the range
+        // the register accepts.
+        unsafe {
+            // start the watchdog, then unlock its configuration
+            iwdg.kr.write(|w| w.bits(0xCCCC));
+            iwdg.kr.write(|w| w.bits(0x5555));
+            iwdg.pr.write(|w| w.bits(pr));
+            iwdg.rlr.write(|w| w.bits(reload));
+        }
+        // wait for the new prescaler and reload value to take effect
+        while 0 != iwdg.sr.read().bits() {}
+
+        let mut watchdog = IndependentWatchdog { iwdg };
+        watchdog.feed();
+        watchdog
+    }
+
+    /// Returns the task that starved, if the last reset was the watchdog's
+    /// doing and a `Supervisor` recorded one, and forgets it.
+    pub fn take_starved() -> Option<TaskId> {
+        // IWDGRSTF: reset by the independent watchdog
+        // This is safe because it's only a read.
+        let by_watchdog = 0 != unsafe { (*RCC::ptr()).csr.read().bits() } & (1 << 29);
+        // This is safe because the record is plain integers, so any bit
+        // pattern is valid, and the main thread is its only user.
+        unsafe {
+            let record = ptr::addr_of_mut!(RECORD).cast::<[u32; 2]>();
+            let [magic, task] = ptr::read_volatile(record);
+            ptr::write_volatile(record, [0, 0]);
+            if by_watchdog && RECORD_MAGIC == magic {
+                Some(TaskId(task as u8))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "board")]
+impl Watchdog for IndependentWatchdog {
+    fn feed(&mut self) {
+        // This is safe because 0xAAAA is the reload key.
+        self.iwdg.kr.write(|w| unsafe { w.bits(0xAAAA) });
+    }
+
+    fn starving(&mut self, task: TaskId) {
+        // This is safe because the main thread is the record's only user.
+        unsafe {
+            let record = ptr::addr_of_mut!(RECORD).cast::<[u32; 2]>();
+            ptr::write_volatile(record, [RECORD_MAGIC, u32::from(task.0)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::{ManualClock, TimeSource};
+
+    /// Counts what a `Supervisor` does to it.
+    #[derive(Default)]
+    struct MockWatchdog {
+        fed: u32,
+        starving: Option<TaskId>,
+        starving_calls: u32,
+    }
+
+    impl Watchdog for MockWatchdog {
+        fn feed(&mut self) {
+            self.fed += 1;
+        }
+
+        fn starving(&mut self, task: TaskId) {
+            self.starving = Some(task);
+            self.starving_calls += 1;
+        }
+    }
+
+    #[test]
+    fn fed_while_every_task_checks_in() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut supervisor: Supervisor<_, 2> =
+            Supervisor::new(MockWatchdog::default(), clock.now());
+        let fast = supervisor.register(Duration::from_ms(10)).unwrap();
+        let slow = supervisor.register(Duration::from_ms(50)).unwrap();
+
+        for ms in 1..=200 {
+            clock.advance(Duration::from_ms(1));
+            supervisor.check_in(fast, clock.now());
+            if 0 == ms % 40 {
+                supervisor.check_in(slow, clock.now());
+            }
+            assert_eq!(None, supervisor.update(clock.now()));
+        }
+        assert_eq!(200, supervisor.watchdog().fed);
+        assert_eq!(0, supervisor.watchdog().starving_calls);
+    }
+
+    #[test]
+    fn late_task_stops_feeding() {
+        let clock = ManualClock::new(Instant::from_ms(0));
+        let mut supervisor: Supervisor<_, 2> =
+            Supervisor::new(MockWatchdog::default(), clock.now());
+        let display = supervisor.register(Duration::from_ms(10)).unwrap();
+        let input = supervisor.register(Duration::from_ms(10)).unwrap();
+
+        for _ in 0..30 {
+            clock.advance(Duration::from_ms(1));
+            supervisor.check_in(display, clock.now());
+            // `input` hangs after 5 ms
+            if clock.now() <= Instant::from_ms(5) {
+                supervisor.check_in(input, clock.now());
+            }
+            supervisor.update(clock.now());
+        }
+        // fed until `input` was more than 10 ms late, at 16 ms
+        assert_eq!(15, supervisor.watchdog().fed);
+        assert_eq!(Some(input), supervisor.starved());
+        assert_eq!(Some(input), supervisor.watchdog().starving);
+        assert_eq!(1, supervisor.watchdog().starving_calls);
+
+        // recovering doesn't start the feeding again
+        supervisor.check_in(input, clock.now());
+        assert_eq!(Some(input), supervisor.update(clock.now()));
+        assert_eq!(15, supervisor.watchdog().fed);
+        assert_eq!(1, supervisor.watchdog().starving_calls);
+    }
+}